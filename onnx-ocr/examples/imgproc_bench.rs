@@ -0,0 +1,80 @@
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+use ndarray::Array4;
+use onnx_ocr::common::imgproc::{ChannelOrder, NormalizeParams, normalize_to_nchw};
+use std::time::{Duration, SystemTime};
+
+// the preprocessing text detection used before `normalize_to_nchw`:
+// u8 -> f32 bgr image, per pixel normalize, then copy pixel by pixel into the tensor
+fn normalize_per_pixel(img: &RgbImage) -> Array4<f32> {
+    let (width, height) = img.dimensions();
+    let mut rgb32f_image: Rgb32FImage = ImageBuffer::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel_u8 = img.get_pixel(x, y);
+            let r_f32 = pixel_u8[0] as f32 / 255.0;
+            let g_f32 = pixel_u8[1] as f32 / 255.0;
+            let b_f32 = pixel_u8[2] as f32 / 255.0;
+            rgb32f_image.put_pixel(x, y, Rgb([b_f32, g_f32, r_f32]));
+        }
+    }
+
+    let alpha = [1.0 / 0.229, 1.0 / 0.224, 1.0 / 0.225];
+    let beta = [-0.485 / 0.229, -0.456 / 0.224, -0.406 / 0.225];
+    let mut nimg = Rgb32FImage::new(width, height);
+    for x in 0..width {
+        for y in 0..height {
+            let [r, g, b] = rgb32f_image.get_pixel(x, y).0;
+            let r = r * alpha[0] + beta[0];
+            let g = g * alpha[1] + beta[1];
+            let b = b * alpha[2] + beta[2];
+            nimg.put_pixel(x, y, Rgb([r, g, b]));
+        }
+    }
+
+    let mut input = Array4::<f32>::zeros((1, 3, height as usize, width as usize));
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let [r, g, b] = nimg.get_pixel(x as u32, y as u32).0;
+            input[[0, 0, y, x]] = r;
+            input[[0, 1, y, x]] = g;
+            input[[0, 2, y, x]] = b;
+        }
+    }
+    input
+}
+
+fn main() {
+    let sizes = [(224, 224), (640, 640), (800, 800), (960, 1280)];
+    let params = NormalizeParams::imagenet(ChannelOrder::Bgr);
+    let rounds = 20;
+    for (width, height) in sizes {
+        let img = RgbImage::from_fn(width, height, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8])
+        });
+
+        let mut total_pixel = Duration::new(0, 0);
+        let mut total_vec = Duration::new(0, 0);
+        for _ in 0..rounds {
+            let start = SystemTime::now();
+            let expected = normalize_per_pixel(&img);
+            total_pixel += SystemTime::now().duration_since(start).expect("time error");
+
+            let start = SystemTime::now();
+            let actual = normalize_to_nchw(&img, &params);
+            total_vec += SystemTime::now().duration_since(start).expect("time error");
+
+            let max_diff = (&expected - &actual)
+                .mapv(f32::abs)
+                .fold(0.0_f32, |a, b| a.max(*b));
+            assert!(max_diff < 1e-4, "results differ by {}", max_diff);
+        }
+        println!(
+            "{}x{}: per pixel {:?}, vectorized {:?}, speedup {:.1}x",
+            width,
+            height,
+            total_pixel / rounds,
+            total_vec / rounds,
+            total_pixel.as_secs_f64() / total_vec.as_secs_f64()
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use crate::doc_layout::predictor::LayoutLabel;
 use crate::model_context::ModelContext;
+use crate::pdf::parser::{RENDER_DPI, pdf_outline, pdf_to_images};
 use crate::pipeline::layout_parsing::doc_analysise::LayoutParser;
+use crate::pipeline::ocr::LanguageRouting;
 use anyhow::Result;
 use image::ImageReader;
 use std::io::Cursor;
@@ -15,3 +17,23 @@ pub fn image_to_markdown(buffer: &[u8]) -> Result<String> {
     let markdown = res.to_markdown()?;
     return Ok(markdown);
 }
+
+pub fn pdf_to_markdown(
+    buffer: &[u8],
+    password: Option<&str>,
+    routing: LanguageRouting,
+) -> Result<String> {
+    let images = pdf_to_images(buffer, password)?;
+    let context = ModelContext::new()?;
+    let mut parser = LayoutParser::new(&context);
+    parser.set_language_routing(routing);
+    // the pages are rendered at RENDER_DPI, table tolerances are scaled from it
+    parser.set_dpi(RENDER_DPI);
+    parser.set_outline(pdf_outline(buffer, password)?);
+    let pages = parser.parse_pages(images.as_slice())?;
+    let mut markdown = String::new();
+    for page in pages.iter() {
+        markdown.push_str(page.to_markdown()?.as_str());
+    }
+    Ok(markdown)
+}
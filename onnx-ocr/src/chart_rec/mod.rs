@@ -0,0 +1,3 @@
+mod postprocess;
+pub mod predictor;
+mod preprocess;
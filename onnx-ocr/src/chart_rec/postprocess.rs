@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use ndarray::ArrayView1;
+
+use crate::formula_rec::postprocess::byte_decoder;
+
+// qwen style special tokens, the text ends at the first end token
+const END_TOKENS: [&str; 2] = ["<|im_end|>", "<|endoftext|>"];
+
+pub struct PostProcessor {
+    character_dict: HashMap<u32, String>,
+    byte_decoder: HashMap<char, u8>,
+}
+
+impl PostProcessor {
+    pub fn new(character_dict: HashMap<u32, String>) -> Self {
+        PostProcessor {
+            character_dict,
+            byte_decoder: byte_decoder(),
+        }
+    }
+
+    // decode the token ids to the table text and split it into rows of cells
+    pub fn process(&self, token_ids: ArrayView1<i64>) -> Vec<Vec<String>> {
+        let mut bytes = Vec::new();
+        for id in token_ids.iter() {
+            let Some(token) = self.character_dict.get(&(*id as u32)) else {
+                continue;
+            };
+            if END_TOKENS.contains(&token.as_str()) {
+                break;
+            }
+            if token.starts_with("<|") && token.ends_with("|>") {
+                continue;
+            }
+            for c in token.chars() {
+                match self.byte_decoder.get(&c) {
+                    Some(b) => bytes.push(*b),
+                    None => {
+                        let mut buf = [0_u8; 4];
+                        bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    }
+                }
+            }
+        }
+        let text = String::from_utf8_lossy(&bytes);
+        parse_table_text(text.as_ref())
+    }
+}
+
+// "Year | Sales\n2020 | 12" like text, the model writes new lines as `<0x0A>` at times and
+// markdown separator rows and outer pipes are tolerated
+pub fn parse_table_text(text: &str) -> Vec<Vec<String>> {
+    let text = text.replace("<0x0A>", "\n");
+    let mut rows = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let line = line.strip_prefix('|').unwrap_or(line);
+        let line = line.strip_suffix('|').unwrap_or(line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cells: Vec<String> = line.split('|').map(|c| c.trim().to_string()).collect();
+        // markdown header separator
+        if cells
+            .iter()
+            .all(|c| !c.is_empty() && c.chars().all(|ch| matches!(ch, '-' | ':')))
+        {
+            continue;
+        }
+        rows.push(cells);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_table_text_splits_rows_and_cells() {
+        let rows = parse_table_text("Year | Sales<0x0A>2020 | 12\n2021 | 15");
+        assert_eq!(
+            rows,
+            vec![
+                vec!["Year".to_string(), "Sales".to_string()],
+                vec!["2020".to_string(), "12".to_string()],
+                vec!["2021".to_string(), "15".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_table_text_skips_markdown_decoration() {
+        let rows = parse_table_text("| a | b |\n|---|:---:|\n\n| 1 | 2 |");
+        assert_eq!(
+            rows,
+            vec![
+                vec!["a".to_string(), "b".to_string()],
+                vec!["1".to_string(), "2".to_string()],
+            ]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use image::RgbImage;
+use ndarray::{Array4, Axis, Ix2, Ix3};
+use ort::{inputs, session::Session, value::TensorRef};
+use std::{cell::RefCell, collections::HashMap, fs::File, io::BufReader, path::Path, rc::Rc};
+
+use crate::{
+    chart_rec::{postprocess::PostProcessor, preprocess::PreProcessor},
+    common::onnx::load_session,
+    formula_rec::postprocess::argmax_token_ids,
+};
+
+// rows of cells read from a chart, the first row holds the series names
+pub type ChartRows = Vec<Vec<String>>;
+
+pub struct ChartRecognitionPredictor {
+    sess: Rc<RefCell<Session>>,
+    pre_processor: PreProcessor,
+    post_processor: PostProcessor,
+}
+
+fn load_character_dict<P: AsRef<Path>>(path: P) -> Result<HashMap<u32, String>> {
+    let f = File::open(path)?;
+    let reader = BufReader::new(f);
+    let result: HashMap<u32, String> = serde_json::from_reader(reader)?;
+    Ok(result)
+}
+
+impl ChartRecognitionPredictor {
+    pub fn try_new<P: AsRef<Path>>(model_path: P, character_path: P) -> Result<Self> {
+        let sess = Rc::new(RefCell::new(load_session(model_path)?));
+        let pre_processor = PreProcessor::default();
+        let character_dict = load_character_dict(character_path)?;
+        let post_processor = PostProcessor::new(character_dict);
+        Ok(Self {
+            sess,
+            pre_processor,
+            post_processor,
+        })
+    }
+
+    // read the data table of bar, line and pie charts. like the formula model the exported
+    // model runs the greedy decoder itself, per step logits are decoded here with argmax.
+    pub fn predict(&self, images: Vec<RgbImage>) -> Result<Vec<ChartRows>> {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
+        let [height, width] = self.pre_processor.input_size();
+        let mut input = Array4::<f32>::zeros((images.len(), 3, height as usize, width as usize));
+        for (i, img) in images.iter().enumerate() {
+            self.pre_processor
+                .process_into(img, input.index_axis_mut(Axis(0), i));
+        }
+
+        let mut sess = self.sess.borrow_mut();
+        let outputs = sess.run(inputs!["x" => TensorRef::from_array_view(&input)?])?;
+        let output = &outputs["fetch_name_0"];
+        let token_ids = match output.try_extract_array::<i64>() {
+            Ok(ids) => ids.into_dimensionality::<Ix2>()?.to_owned(),
+            Err(_) => {
+                let logits = output.try_extract_array::<f32>()?;
+                argmax_token_ids(logits.into_dimensionality::<Ix3>()?)
+            }
+        };
+
+        let rows = token_ids
+            .axis_iter(Axis(0))
+            .map(|ids| self.post_processor.process(ids))
+            .collect();
+        Ok(rows)
+    }
+}
@@ -0,0 +1,41 @@
+use image::{
+    RgbImage,
+    imageops::{FilterType, resize},
+};
+use ndarray::ArrayViewMut3;
+
+use crate::common::imgproc::{ChannelOrder, NormalizeParams, normalize_into};
+
+pub struct PreProcessor {
+    // [height, width] of the model input
+    input_size: [u32; 2],
+    normalize_params: NormalizeParams,
+}
+
+impl Default for PreProcessor {
+    fn default() -> Self {
+        PreProcessor {
+            input_size: [1024, 1024],
+            // clip image statistics of the vision encoder
+            normalize_params: NormalizeParams::new(
+                1.0 / 255.0,
+                [0.4814547, 0.4578275, 0.4082107],
+                [0.2686295, 0.2613026, 0.2757771],
+                ChannelOrder::Rgb,
+            ),
+        }
+    }
+}
+
+impl PreProcessor {
+    pub fn input_size(&self) -> [u32; 2] {
+        self.input_size
+    }
+
+    // the chart is stretched to the square input, the encoder was trained without padding
+    pub fn process_into(&self, img: &RgbImage, output: ArrayViewMut3<f32>) {
+        let [height, width] = self.input_size;
+        let img = resize(img, width, height, FilterType::CatmullRom);
+        normalize_into(&img, &self.normalize_params, output);
+    }
+}
@@ -2,10 +2,11 @@ use std::io::BufReader;
 use std::path::Path;
 
 use anyhow::Result;
-use image::{GenericImage, RgbImage};
-use image::{ImageBuffer, ImageReader, Pixel, Rgb, Rgb32FImage};
+use image::RgbImage;
+use image::{ImageBuffer, ImageReader, Pixel};
 use imageproc::definitions::Image;
 use imageproc::point::Point;
+use ndarray::{Array4, ArrayView3, ArrayViewMut3, Axis, Zip, s};
 
 pub fn load_image<P: AsRef<Path>>(img_path: P) -> Result<RgbImage> {
     let img_reader = BufReader::new(std::fs::File::open(img_path).unwrap());
@@ -15,20 +16,85 @@ pub fn load_image<P: AsRef<Path>>(img_path: P) -> Result<RgbImage> {
     Ok(rgb)
 }
 
-pub fn convert_rgb_to_rgb32f(rgb_image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Rgb32FImage {
-    let (width, height) = rgb_image.dimensions();
-    let mut rgb32f_image = ImageBuffer::new(width, height);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelOrder {
+    Rgb,
+    Bgr,
+}
+
+// output = (pixel * scale - mean) / std, mean and std are given in output channel order
+#[derive(Debug, Clone)]
+pub struct NormalizeParams {
+    pub scale: f32,
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+    pub channel_order: ChannelOrder,
+}
 
-    for y in 0..height {
-        for x in 0..width {
-            let pixel_u8 = rgb_image.get_pixel(x, y);
-            let r_f32 = pixel_u8[0] as f32 / 255.0;
-            let g_f32 = pixel_u8[1] as f32 / 255.0;
-            let b_f32 = pixel_u8[2] as f32 / 255.0;
-            rgb32f_image.put_pixel(x, y, Rgb([b_f32, g_f32, r_f32]));
+impl NormalizeParams {
+    pub fn new(scale: f32, mean: [f32; 3], std: [f32; 3], channel_order: ChannelOrder) -> Self {
+        NormalizeParams {
+            scale,
+            mean,
+            std,
+            channel_order,
         }
     }
-    rgb32f_image
+
+    pub fn imagenet(channel_order: ChannelOrder) -> Self {
+        Self::new(
+            1.0 / 255.0,
+            [0.485, 0.456, 0.406],
+            [0.229, 0.224, 0.225],
+            channel_order,
+        )
+    }
+
+    pub fn unit(channel_order: ChannelOrder) -> Self {
+        Self::new(1.0 / 255.0, [0.0; 3], [1.0; 3], channel_order)
+    }
+}
+
+pub fn normalize_to_nchw(img: &RgbImage, params: &NormalizeParams) -> Array4<f32> {
+    let (width, height) = img.dimensions();
+    let mut output = Array4::<f32>::zeros((1, 3, height as usize, width as usize));
+    normalize_into(img, params, output.index_axis_mut(Axis(0), 0));
+    output
+}
+
+// write the normalized CHW image into the top left corner of `output`,
+// the rest of `output` is left untouched so callers can use it for padding
+pub fn normalize_into(img: &RgbImage, params: &NormalizeParams, mut output: ArrayViewMut3<f32>) {
+    let (width, height) = img.dimensions();
+    let (width, height) = (width as usize, height as usize);
+    let raw = img.as_raw();
+    let src_channels = match params.channel_order {
+        ChannelOrder::Rgb => [0, 1, 2],
+        ChannelOrder::Bgr => [2, 1, 0],
+    };
+    let alpha: [f32; 3] = std::array::from_fn(|c| params.scale / params.std[c]);
+    let beta: [f32; 3] = std::array::from_fn(|c| -params.mean[c] / params.std[c]);
+
+    let mut target = output.slice_mut(s![.., ..height, ..width]);
+    if let Some(dst) = target.as_slice_mut() {
+        // no padding around the image, fill the three planes in a single pass
+        let (p0, rest) = dst.split_at_mut(height * width);
+        let (p1, p2) = rest.split_at_mut(height * width);
+        for (((px, d0), d1), d2) in raw.chunks_exact(3).zip(p0).zip(p1).zip(p2) {
+            *d0 = px[src_channels[0]] as f32 * alpha[0] + beta[0];
+            *d1 = px[src_channels[1]] as f32 * alpha[1] + beta[1];
+            *d2 = px[src_channels[2]] as f32 * alpha[2] + beta[2];
+        }
+        return;
+    }
+    let hwc = ArrayView3::from_shape((height, width, 3), raw.as_slice())
+        .expect("rgb image buffer matches its dimensions");
+    for c in 0..3 {
+        let (alpha, beta) = (alpha[c], beta[c]);
+        Zip::from(target.index_axis_mut(Axis(0), c))
+            .and(hwc.index_axis(Axis(2), src_channels[c]))
+            .for_each(|d, v| *d = *v as f32 * alpha + beta);
+    }
 }
 
 pub fn pointu32_to_pintf32(point: &Point<u32>) -> Point<f32> {
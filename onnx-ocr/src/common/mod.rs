@@ -4,3 +4,4 @@ pub mod imgproc;
 pub mod min_area_rect;
 pub mod onnx;
 pub mod quad;
+pub mod zip;
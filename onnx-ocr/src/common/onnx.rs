@@ -1,4 +1,5 @@
 use anyhow::Result;
+use ndarray::{Array2, s};
 use ort::session::{Session, builder::GraphOptimizationLevel};
 use std::path::Path;
 
@@ -9,3 +10,16 @@ pub fn load_session<P: AsRef<Path>>(model_path: P) -> Result<Session> {
         .commit_from_file(model_path)?;
     Ok(sess)
 }
+
+// paddle detection models return the boxes of the whole batch stacked in one array,
+// `bbox_num` holds how many rows belong to each image
+pub fn split_batch_boxes(boxes: &Array2<f32>, bbox_num: &[i32]) -> Vec<Array2<f32>> {
+    let mut result = Vec::with_capacity(bbox_num.len());
+    let mut start = 0;
+    for num in bbox_num.iter() {
+        let end = (start + (*num).max(0) as usize).min(boxes.nrows());
+        result.push(boxes.slice(s![start..end, ..]).to_owned());
+        start = end;
+    }
+    result
+}
@@ -47,6 +47,18 @@ impl Quad {
             height,
         }
     }
+    // transform every corner, the corners are then renamed so `tl` is again the top left one
+    pub fn map_points<F: Fn(Point<f32>) -> Point<f32>>(&self, f: F) -> Self {
+        let mut points = [f(self.tl), f(self.tr), f(self.dr), f(self.dl)];
+        let first = points
+            .iter()
+            .enumerate()
+            .min_by(|a, b| (a.1.x + a.1.y).total_cmp(&(b.1.x + b.1.y)))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        points.rotate_left(first);
+        Quad::new(points[0], points[1], points[2], points[3])
+    }
     pub fn projection(&self) -> Option<Projection> {
         let to = [
             (0.0, 0.0),
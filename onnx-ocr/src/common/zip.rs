@@ -0,0 +1,103 @@
+use anyhow::{Result, bail};
+
+// minimal zip archive writer, entries are stored without compression which is enough for
+// small office documents
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+#[derive(Default)]
+pub struct ZipWriter {
+    buf: Vec<u8>,
+    entries: Vec<ZipEntry>,
+}
+
+// 1980-01-01 00:00, the earliest dos timestamp
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+// names are utf-8
+const FLAGS: u16 = 1 << 11;
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        if self.buf.len() + data.len() > u32::MAX as usize {
+            bail!("zip archive larger than 4GB is not supported");
+        }
+        let crc = crc32fast::hash(data);
+        let size = data.len() as u32;
+        let offset = self.buf.len() as u32;
+
+        self.put_u32(0x04034b50);
+        self.put_u16(20);
+        self.put_u16(FLAGS);
+        self.put_u16(0);
+        self.put_u16(DOS_TIME);
+        self.put_u16(DOS_DATE);
+        self.put_u32(crc);
+        self.put_u32(size);
+        self.put_u32(size);
+        self.put_u16(name.len() as u16);
+        self.put_u16(0);
+        self.buf.extend_from_slice(name.as_bytes());
+        self.buf.extend_from_slice(data);
+
+        self.entries.push(ZipEntry {
+            name: name.to_string(),
+            crc,
+            size,
+            offset,
+        });
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let central_offset = self.buf.len() as u32;
+        let entries = std::mem::take(&mut self.entries);
+        for entry in entries.iter() {
+            self.put_u32(0x02014b50);
+            self.put_u16(20);
+            self.put_u16(20);
+            self.put_u16(FLAGS);
+            self.put_u16(0);
+            self.put_u16(DOS_TIME);
+            self.put_u16(DOS_DATE);
+            self.put_u32(entry.crc);
+            self.put_u32(entry.size);
+            self.put_u32(entry.size);
+            self.put_u16(entry.name.len() as u16);
+            self.put_u16(0);
+            self.put_u16(0);
+            self.put_u16(0);
+            self.put_u16(0);
+            self.put_u32(0);
+            self.put_u32(entry.offset);
+            self.buf.extend_from_slice(entry.name.as_bytes());
+        }
+        let central_size = self.buf.len() as u32 - central_offset;
+
+        self.put_u32(0x06054b50);
+        self.put_u16(0);
+        self.put_u16(0);
+        self.put_u16(entries.len() as u16);
+        self.put_u16(entries.len() as u16);
+        self.put_u32(central_size);
+        self.put_u32(central_offset);
+        self.put_u16(0);
+        self.buf
+    }
+
+    fn put_u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn put_u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+}
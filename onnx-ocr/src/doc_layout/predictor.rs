@@ -2,11 +2,14 @@ use std::{cell::RefCell, path::Path, rc::Rc};
 
 use anyhow::Result;
 use image::RgbImage;
-use ndarray::Ix2;
+use ndarray::{Array2, Axis, Ix2, concatenate};
 use ort::{inputs, session::Session, value::Tensor, value::TensorRef};
 
 use crate::{
-    common::{imgproc::load_image, onnx::load_session},
+    common::{
+        imgproc::load_image,
+        onnx::{load_session, split_batch_boxes},
+    },
     doc_layout::{postprocess::PostProcessor, preprocess::PreProcessor},
 };
 
@@ -128,23 +131,59 @@ impl LayoutPredictor {
     }
 
     pub fn predict_image(&self, img: &RgbImage) -> Result<Vec<LayoutResult>> {
-        let img_width = img.width();
-        let img_height = img.height();
-        let pre_output = self.pre_processor.process(&img)?;
-        let input = pre_output.get_input_as_ndarray();
+        let mut results = self.predict_batch(std::slice::from_ref(img))?;
+        Ok(results.remove(0))
+    }
+
+    pub fn predict_batch(&self, images: &[RgbImage]) -> Result<Vec<Vec<LayoutResult>>> {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
+        let batch_size = images.len();
+        let mut pre_outputs = Vec::with_capacity(batch_size);
+        for img in images.iter() {
+            pre_outputs.push(self.pre_processor.process(img)?);
+        }
+        // all images are resized to the same 800x800 input
+        let views: Vec<_> = pre_outputs.iter().map(|p| p.input.view()).collect();
+        let input = concatenate(Axis(0), views.as_slice())?;
+        let im_shape: Vec<f32> = pre_outputs
+            .iter()
+            .flat_map(|p| [p.input_height as f32, p.input_width as f32])
+            .collect();
+        let scale_factor: Vec<f32> = pre_outputs
+            .iter()
+            .flat_map(|p| [p.ratio_h, p.ratio_w])
+            .collect();
+
         let mut sess = self.sess.borrow_mut();
-        let outputs =sess
-            .run(inputs![
-                "image" =>TensorRef::from_array_view(&input).unwrap(), 
-                "im_shape"=>Tensor::from_array(([1, 2],vec![800.0_f32,800.0])).unwrap(),
-                "scale_factor"=>Tensor::from_array(([1, 2],vec![pre_output.ratio_h,pre_output.ratio_w])).unwrap()]
-            ).unwrap();
+        let outputs = sess.run(inputs![
+            "image" => TensorRef::from_array_view(&input)?,
+            "im_shape" => Tensor::from_array(([batch_size, 2], im_shape))?,
+            "scale_factor" => Tensor::from_array(([batch_size, 2], scale_factor))?
+        ])?;
 
-        let output = outputs["fetch_name_0"].try_extract_array::<f32>().unwrap();
+        let output = outputs["fetch_name_0"].try_extract_array::<f32>()?;
+        let boxes = output.into_dimensionality::<Ix2>()?.to_owned();
+        let bbox_num: Vec<i32> = match outputs.get("fetch_name_1") {
+            Some(num) => num.try_extract_array::<i32>()?.iter().copied().collect(),
+            None => vec![boxes.nrows() as i32],
+        };
+
+        let mut results = Vec::with_capacity(batch_size);
+        for (img, bitmap) in images.iter().zip(split_batch_boxes(&boxes, &bbox_num)) {
+            results.push(self.postprocess(&bitmap, img.width(), img.height())?);
+        }
+        Ok(results)
+    }
 
-        let preds = output.squeeze();
-        let bitmap = preds.into_dimensionality::<Ix2>()?.to_owned();
-        let boxes_result = self.post_processor.process(&bitmap)?;
+    fn postprocess(
+        &self,
+        bitmap: &Array2<f32>,
+        img_width: u32,
+        img_height: u32,
+    ) -> Result<Vec<LayoutResult>> {
+        let boxes_result = self.post_processor.process(bitmap)?;
         let mut results = Vec::new();
         let area_thresh = if img_width > img_height { 0.82 } else { 0.93 };
         let img_area = (img_width * img_height) as f32;
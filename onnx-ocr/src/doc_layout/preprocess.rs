@@ -1,13 +1,15 @@
 use anyhow::Result;
 use image::{
-    Rgb, Rgb32FImage, RgbImage,
+    RgbImage,
     imageops::{FilterType, resize},
 };
 use ndarray::Array4;
 
-use crate::common::imgproc::convert_rgb_to_rgb32f;
+use crate::common::imgproc::{ChannelOrder, NormalizeParams, normalize_to_nchw};
 
-pub struct PreProcessor {}
+pub struct PreProcessor {
+    normalize_params: NormalizeParams,
+}
 
 pub struct PreOutput {
     pub ori_img: RgbImage,
@@ -15,29 +17,14 @@ pub struct PreOutput {
     pub ratio_w: f32,
     pub input_width: u32,
     pub input_height: u32,
-    pub input: Rgb32FImage,
-}
-impl PreOutput {
-    pub fn get_input_as_ndarray(&self) -> Array4<f32> {
-        let mut input =
-            Array4::<f32>::zeros((1, 3, self.input_height as usize, self.input_width as usize));
-
-        for y in 0..self.input_height as usize {
-            for x in 0..self.input_width as usize {
-                let pixel = self.input.get_pixel(x as u32, y as u32);
-                let [r, g, b] = pixel.0;
-                input[[0, 0, y, x]] = r;
-                input[[0, 1, y, x]] = g;
-                input[[0, 2, y, x]] = b;
-            }
-        }
-        return input;
-    }
+    pub input: Array4<f32>,
 }
 
 impl Default for PreProcessor {
     fn default() -> Self {
-        PreProcessor {}
+        PreProcessor {
+            normalize_params: NormalizeParams::unit(ChannelOrder::Bgr),
+        }
     }
 }
 
@@ -59,7 +46,7 @@ impl PreProcessor {
             input_height: rh,
             ratio_w: rw as f32 / w as f32,
             ratio_h: rh as f32 / h as f32,
-            input: convert_rgb_to_rgb32f(&rimg),
+            input: normalize_to_nchw(&rimg, &self.normalize_params),
         };
         return Ok(result);
     }
@@ -4,7 +4,6 @@ use crate::{
 };
 use anyhow::Result;
 use image::RgbImage;
-use ndarray::Array4;
 use ort::{inputs, session::Session, value::TensorRef};
 use std::{cell::RefCell, path::Path, rc::Rc};
 
@@ -37,20 +36,7 @@ impl DocTextOriPredictor {
     }
 
     pub fn predict_image(&self, img: &RgbImage) -> Result<RotateAngle> {
-        let input_img = self.pre_processor.process(&img);
-
-        let input_width = input_img.width();
-        let input_height = input_img.height();
-        let mut input = Array4::<f32>::zeros((1, 3, input_height as usize, input_width as usize));
-        for y in 0..input_height as usize {
-            for x in 0..input_width as usize {
-                let pixel = input_img.get_pixel(x as u32, y as u32);
-                let [r, g, b] = pixel.0;
-                input[[0, 0, y, x]] = r;
-                input[[0, 1, y, x]] = g;
-                input[[0, 2, y, x]] = b;
-            }
-        }
+        let input = self.pre_processor.process(img);
         let mut sess = self.sess.borrow_mut();
         let outputs = sess
             .run(inputs![
@@ -1,27 +1,28 @@
 use image::{
-    Rgb, Rgb32FImage, RgbImage,
+    RgbImage,
     imageops::{FilterType, crop_imm, resize},
 };
+use ndarray::Array4;
+
+use crate::common::imgproc::{ChannelOrder, NormalizeParams, normalize_to_nchw};
 
 pub struct PreProcessor {
-    alpha: [f32; 3],
-    beta: [f32; 3],
+    normalize_params: NormalizeParams,
 }
 
 impl Default for PreProcessor {
     fn default() -> Self {
-        let alpha = [1.0 / 0.229, 1.0 / 0.224, 1.0 / 0.225];
-        let beta = [-0.485 / 0.229, -0.456 / 0.224, -0.406 / 0.225];
-        PreProcessor { alpha, beta }
+        PreProcessor {
+            normalize_params: NormalizeParams::imagenet(ChannelOrder::Rgb),
+        }
     }
 }
 
 impl PreProcessor {
-    pub fn process(&self, img: &RgbImage) -> Rgb32FImage {
+    pub fn process(&self, img: &RgbImage) -> Array4<f32> {
         let img = self.resize(img);
         let img = self.crop(&img);
-        let img = self.normalize(&img);
-        img
+        normalize_to_nchw(&img, &self.normalize_params)
     }
 
     fn resize(&self, img: &RgbImage) -> RgbImage {
@@ -46,21 +47,4 @@ impl PreProcessor {
         let cimg = crop_imm(img, x1, y1, cw, ch).to_image();
         cimg
     }
-
-    fn normalize(&self, img: &RgbImage) -> Rgb32FImage {
-        let w = img.width();
-        let h = img.height();
-        let mut nimg = Rgb32FImage::new(w, h);
-        for x in 0..w {
-            for y in 0..h {
-                let pixel = img.get_pixel(x, y);
-                let [r, g, b] = pixel.0;
-                let r = (r as f32) / 255.0 * self.alpha[0] + self.beta[0];
-                let g = (g as f32) / 255.0 * self.alpha[1] + self.beta[1];
-                let b = (b as f32) / 255.0 * self.alpha[2] + self.beta[2];
-                nimg.put_pixel(x, y, Rgb([r, g, b]));
-            }
-        }
-        nimg
-    }
 }
@@ -1,3 +1,3 @@
-mod postprocess;
+pub(crate) mod postprocess;
 pub mod predictor;
 mod preprocess;
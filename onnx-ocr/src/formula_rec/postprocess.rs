@@ -1,16 +1,164 @@
-use anyhow::Result;
-use ndarray::Array2;
+use std::collections::HashMap;
 
-pub struct PostProcessor {}
+use ndarray::{Array2, ArrayView1, ArrayView3, Axis};
 
-impl Default for PostProcessor {
-    fn default() -> Self {
-        Self {}
-    }
+const BOS_ID: i64 = 0;
+const PAD_ID: i64 = 1;
+const EOS_ID: i64 = 2;
+const UNK_ID: i64 = 3;
+
+pub struct PostProcessor {
+    character_dict: HashMap<u32, String>,
+    byte_decoder: HashMap<char, u8>,
 }
 
 impl PostProcessor {
-    pub fn process(&self, pred: &Array2<f32>) -> Result<Vec<(u32, f32)>> {
-        unimplemented!()
+    pub fn new(character_dict: HashMap<u32, String>) -> Self {
+        PostProcessor {
+            character_dict,
+            byte_decoder: byte_decoder(),
+        }
+    }
+
+    pub fn process(&self, token_ids: ArrayView1<i64>) -> String {
+        let mut bytes = Vec::new();
+        for id in token_ids.iter() {
+            match *id {
+                EOS_ID => break,
+                BOS_ID | PAD_ID | UNK_ID => continue,
+                id => {
+                    if let Some(token) = self.character_dict.get(&(id as u32)) {
+                        for c in token.chars() {
+                            match self.byte_decoder.get(&c) {
+                                Some(b) => bytes.push(*b),
+                                None => {
+                                    let mut buf = [0_u8; 4];
+                                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let text = String::from_utf8_lossy(&bytes);
+        normalize_latex(text.trim())
+    }
+}
+
+// greedy decoding of the per step logits `[batch, steps, vocab]`
+pub fn argmax_token_ids(logits: ArrayView3<f32>) -> Array2<i64> {
+    let (batch, steps, _) = logits.dim();
+    let mut ids = Array2::<i64>::from_elem((batch, steps), PAD_ID);
+    for (b, sequence) in logits.axis_iter(Axis(0)).enumerate() {
+        for (t, step) in sequence.axis_iter(Axis(0)).enumerate() {
+            let mut max_index = 0;
+            let mut max_value = f32::MIN;
+            for (i, v) in step.iter().enumerate() {
+                if *v > max_value {
+                    max_value = *v;
+                    max_index = i;
+                }
+            }
+            ids[[b, t]] = max_index as i64;
+        }
+    }
+    ids
+}
+
+// inverse of the gpt2 byte level bpe mapping from bytes to printable unicode chars
+pub(crate) fn byte_decoder() -> HashMap<char, u8> {
+    let mut decoder = HashMap::new();
+    let mut n = 0;
+    for b in 0..=255_u8 {
+        let printable = matches!(b, b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF);
+        let c = if printable {
+            b as u32
+        } else {
+            n += 1;
+            255 + n
+        };
+        decoder.insert(char::from_u32(c).unwrap(), b);
+    }
+    decoder
+}
+
+// drop the spaces the tokenizer puts between latex tokens, a space is only kept between
+// two letters (`\alpha x`) or when it is escaped (`\ `). spaces inside `\mathrm{..}`,
+// `\text{..}`, `\operatorname{..}` and `\mathbf{..}` arguments are removed as well.
+pub fn normalize_latex(latex: &str) -> String {
+    let chars: Vec<char> = latex.chars().collect();
+    let mut result = String::with_capacity(latex.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && i + 1 < chars.len() && chars[i + 1] == ' ' {
+            result.push_str("\\ ");
+            i += 2;
+            continue;
+        }
+        if c == '\\'
+            && let Some(end) = text_command_end(&chars, i)
+        {
+            result.extend(chars[i..end].iter().filter(|c| !c.is_whitespace()));
+            i = end;
+            continue;
+        }
+        if c.is_whitespace() {
+            let mut j = i;
+            while j < chars.len() && chars[j].is_whitespace() {
+                j += 1;
+            }
+            let prev = result.chars().last();
+            let next = chars.get(j).copied();
+            if let (Some(prev), Some(next)) = (prev, next)
+                && prev.is_ascii_alphabetic()
+                && next.is_ascii_alphabetic()
+            {
+                result.push(' ');
+            }
+            i = j;
+            continue;
+        }
+        result.push(c);
+        i += 1;
+    }
+    result
+}
+
+// end of `\mathrm {...}` like commands starting at `start`, including the braced argument
+fn text_command_end(chars: &[char], start: usize) -> Option<usize> {
+    const COMMANDS: [&str; 4] = ["operatorname", "mathrm", "text", "mathbf"];
+    let mut i = start + 1;
+    let name_end = chars[i..]
+        .iter()
+        .position(|c| !c.is_ascii_alphabetic())
+        .map(|p| i + p)
+        .unwrap_or(chars.len());
+    let name: String = chars[i..name_end].iter().collect();
+    if !COMMANDS.contains(&name.as_str()) {
+        return None;
+    }
+    i = name_end;
+    while i < chars.len() && (chars[i] == ' ' || chars[i] == '*') {
+        i += 1;
+    }
+    if chars.get(i) != Some(&'{') {
+        return None;
+    }
+    let mut depth = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
     }
+    None
 }
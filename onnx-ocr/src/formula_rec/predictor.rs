@@ -1,18 +1,20 @@
 use anyhow::Result;
 use image::RgbImage;
-use ndarray::{Array4, Ix2};
+use ndarray::{Array4, Axis, Ix2, Ix3};
 use ort::{inputs, session::Session, value::TensorRef};
 use std::{cell::RefCell, collections::HashMap, fs::File, io::BufReader, path::Path, rc::Rc};
 
 use crate::{
     common::imgproc::load_image,
     common::onnx::load_session,
-    formula_rec::{postprocess::PostProcessor, preprocess::PreProcessor},
+    formula_rec::{
+        postprocess::{PostProcessor, argmax_token_ids},
+        preprocess::PreProcessor,
+    },
 };
 
 pub struct FormulaRecognitionPredictor {
     sess: Rc<RefCell<Session>>,
-    character_dict: HashMap<u32, String>,
     pre_processor: PreProcessor,
     post_processor: PostProcessor,
 }
@@ -28,65 +30,49 @@ impl FormulaRecognitionPredictor {
     pub fn try_new<P: AsRef<Path>>(model_path: P, character_path: P) -> Result<Self> {
         let sess = Rc::new(RefCell::new(load_session(model_path)?));
         let pre_processor = PreProcessor::default();
-        let post_processor = PostProcessor::default();
         let character_dict = load_character_dict(character_path)?;
+        let post_processor = PostProcessor::new(character_dict);
         Ok(Self {
             sess,
             pre_processor,
             post_processor,
-            character_dict,
         })
     }
 
-    pub fn predict_path<P: AsRef<Path>>(&mut self, img_path: P) -> Result<Vec<(String, f32)>> {
+    pub fn predict_path<P: AsRef<Path>>(&mut self, img_path: P) -> Result<Vec<String>> {
         let img = load_image(img_path)?;
-        let mut images = Vec::new();
-        images.push(img);
-        self.predict(images)
+        self.predict(vec![img])
     }
 
-    pub fn predict(&self, images: Vec<RgbImage>) -> Result<Vec<(String, f32)>> {
-        let mut predicted_text = Vec::new();
-        for img in images {
-            let input_img = self.pre_processor.process(&img);
-            let height = input_img.height();
-            let width = input_img.width();
-
-            let mut input = Array4::<f32>::zeros((1, 3, height as usize, width as usize));
+    // recognize formula images to latex. the exported model runs the autoregressive greedy
+    // decoder itself and returns the token ids, models returning the per step logits are
+    // decoded here with argmax.
+    pub fn predict(&self, images: Vec<RgbImage>) -> Result<Vec<String>> {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
+        let [height, width] = self.pre_processor.input_size();
+        let mut input = Array4::<f32>::zeros((images.len(), 1, height as usize, width as usize));
+        for (i, img) in images.iter().enumerate() {
+            self.pre_processor
+                .process_into(img, &mut input.index_axis_mut(Axis(0), i));
+        }
 
-            for y in 0..height as usize {
-                for x in 0..width as usize {
-                    let pixel = input_img.get_pixel(x as u32, y as u32);
-                    let [r, g, b] = pixel.0;
-                    input[[0, 0, y, x]] = b;
-                    input[[0, 1, y, x]] = g;
-                    input[[0, 2, y, x]] = r;
-                }
+        let mut sess = self.sess.borrow_mut();
+        let outputs = sess.run(inputs!["x" => TensorRef::from_array_view(&input)?])?;
+        let output = &outputs["fetch_name_0"];
+        let token_ids = match output.try_extract_array::<i64>() {
+            Ok(ids) => ids.into_dimensionality::<Ix2>()?.to_owned(),
+            Err(_) => {
+                let logits = output.try_extract_array::<f32>()?;
+                argmax_token_ids(logits.into_dimensionality::<Ix3>()?)
             }
+        };
 
-            let mut sess = self.sess.borrow_mut();
-            let outputs = sess
-                .run(inputs!["x" => TensorRef::from_array_view(&input)?])
-                .unwrap();
-            let output = outputs["fetch_name_0"].try_extract_array::<f32>()?;
-            let preds = output.squeeze();
-            let preds = preds.into_dimensionality::<Ix2>()?.to_owned();
-            let idx_score = self.post_processor.process(&preds)?;
-            let mut content = String::new();
-            let mut max_score = 0.0;
-            for (id, score) in idx_score.iter() {
-                if *id == 0 {
-                    content.push_str("");
-                } else {
-                    let s = self.character_dict.get(id).unwrap();
-                    content.push_str(s.as_str())
-                }
-                if score > &max_score {
-                    max_score = score.to_owned()
-                }
-            }
-            predicted_text.push((content, max_score));
-        }
-        Ok(predicted_text)
+        let latex = token_ids
+            .axis_iter(Axis(0))
+            .map(|ids| self.post_processor.process(ids))
+            .collect();
+        Ok(latex)
     }
 }
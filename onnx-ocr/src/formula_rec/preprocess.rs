@@ -1,15 +1,130 @@
-use image::{Rgb32FImage, RgbImage};
+use image::{
+    GrayImage, Luma, RgbImage,
+    imageops::{FilterType, crop_imm, overlay, resize},
+};
+use ndarray::{ArrayViewMut3, s};
 
-pub struct PreProcessor {}
+pub struct PreProcessor {
+    // [height, width] of the model input
+    input_size: [u32; 2],
+    mean: f32,
+    std: f32,
+}
 
 impl Default for PreProcessor {
     fn default() -> Self {
-        PreProcessor {}
+        PreProcessor {
+            input_size: [768, 768],
+            mean: 0.7931,
+            std: 0.1738,
+        }
     }
 }
 
 impl PreProcessor {
-    pub fn process(&self, img: &RgbImage) -> Rgb32FImage {
-        unimplemented!()
+    pub fn input_size(&self) -> [u32; 2] {
+        self.input_size
+    }
+
+    pub fn process_into(&self, img: &RgbImage, output: &mut ArrayViewMut3<f32>) {
+        let gray = image::DynamicImage::ImageRgb8(img.to_owned()).to_luma8();
+        let gray = self.crop_margin(&gray);
+        let gray = self.resize(&gray);
+        let gray = self.pad(&gray);
+        let (width, height) = gray.dimensions();
+        let alpha = 1.0 / 255.0 / self.std;
+        let beta = -self.mean / self.std;
+        let mut plane = output.slice_mut(s![0, ..height as usize, ..width as usize]);
+        for (d, v) in plane.iter_mut().zip(gray.as_raw().iter()) {
+            *d = *v as f32 * alpha + beta;
+        }
+    }
+
+    // remove the white border around the formula
+    fn crop_margin(&self, img: &GrayImage) -> GrayImage {
+        let (width, height) = img.dimensions();
+        if width == 0 || height == 0 {
+            return img.to_owned();
+        }
+        let (min_v, max_v) = img
+            .as_raw()
+            .iter()
+            .fold((u8::MAX, u8::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+        if max_v == min_v {
+            return img.to_owned();
+        }
+        let range = (max_v - min_v) as f32;
+        let (mut x1, mut y1, mut x2, mut y2) = (width, height, 0, 0);
+        for (x, y, p) in img.enumerate_pixels() {
+            let v = (p.0[0] - min_v) as f32 / range * 255.0;
+            if v < 200.0 {
+                x1 = x1.min(x);
+                y1 = y1.min(y);
+                x2 = x2.max(x);
+                y2 = y2.max(y);
+            }
+        }
+        if x2 < x1 || y2 < y1 {
+            return img.to_owned();
+        }
+        crop_imm(img, x1, y1, x2 - x1 + 1, y2 - y1 + 1).to_image()
+    }
+
+    // scale the short edge to the short side of the input, then shrink to fit the input
+    fn resize(&self, img: &GrayImage) -> GrayImage {
+        let [th, tw] = self.input_size;
+        let (w, h) = img.dimensions();
+        let mut scale = th.min(tw) as f32 / h.min(w) as f32;
+        let fit = (tw as f32 / (w as f32 * scale)).min(th as f32 / (h as f32 * scale));
+        if fit < 1.0 {
+            scale *= fit;
+        }
+        let rw = ((w as f32 * scale).round() as u32).clamp(1, tw);
+        let rh = ((h as f32 * scale).round() as u32).clamp(1, th);
+        resize(img, rw, rh, FilterType::Triangle)
+    }
+
+    // center the image on a black canvas of the input size
+    fn pad(&self, img: &GrayImage) -> GrayImage {
+        let [th, tw] = self.input_size;
+        let (w, h) = img.dimensions();
+        let mut canvas = GrayImage::from_pixel(tw, th, Luma([0]));
+        overlay(
+            &mut canvas,
+            img,
+            ((tw - w) / 2) as i64,
+            ((th - h) / 2) as i64,
+        );
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crop_margin_keeps_empty_crops() {
+        let processor = PreProcessor::default();
+        assert_eq!(
+            processor.crop_margin(&GrayImage::new(0, 12)).dimensions(),
+            (0, 12)
+        );
+        assert_eq!(
+            processor.crop_margin(&GrayImage::new(12, 0)).dimensions(),
+            (12, 0)
+        );
+    }
+
+    #[test]
+    fn crop_margin_removes_the_white_border() {
+        let mut img = GrayImage::from_pixel(20, 10, Luma([255]));
+        for x in 5..9 {
+            img.put_pixel(x, 4, Luma([0]));
+        }
+        assert_eq!(
+            PreProcessor::default().crop_margin(&img).dimensions(),
+            (4, 1)
+        );
     }
 }
@@ -1,4 +1,5 @@
 pub mod api;
+pub mod chart_rec;
 pub mod common;
 pub mod doc_layout;
 pub mod doc_text_ori;
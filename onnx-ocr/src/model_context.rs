@@ -1,16 +1,23 @@
 use crate::{
-    doc_layout::predictor::LayoutPredictor, doc_text_ori::predictor::DocTextOriPredictor,
-    settings::Settings, table_cell_detection::predictor::TableCellDetector,
-    table_cls::predictor::TableClsPredictor, table_structure::predictor::TableStructurePredictor,
+    chart_rec::predictor::ChartRecognitionPredictor,
+    doc_layout::predictor::LayoutPredictor,
+    doc_text_ori::predictor::DocTextOriPredictor,
+    formula_rec::predictor::FormulaRecognitionPredictor,
+    settings::Settings,
+    table_cell_detection::predictor::TableCellDetector,
+    table_cls::predictor::TableClsPredictor,
+    table_structure::predictor::TableStructurePredictor,
     text_detection::predictor::TextDetectionPredictor,
-    text_recognition::predictor::TextRecognitionPredictor,
+    text_recognition::predictor::{Language, TextRecognitionPredictor},
 };
 use anyhow::Result;
+use std::{collections::HashMap, path::Path};
 
 pub struct ModelContext {
     settings: Settings,
     pub text_det_predictor: TextDetectionPredictor,
     pub text_rec_predictor: TextRecognitionPredictor,
+    lang_text_rec_predictors: HashMap<Language, TextRecognitionPredictor>,
     pub layout_predictor: LayoutPredictor,
     pub table_cls_predictor: TableClsPredictor,
     pub wired_table_cell_predictor: TableCellDetector,
@@ -18,6 +25,10 @@ pub struct ModelContext {
     pub wired_table_structure_predictor: TableStructurePredictor,
     pub wireless_table_structure_predictor: TableStructurePredictor,
     pub doc_text_ori_predictor: DocTextOriPredictor,
+    // optional, formulas are kept as ocr text when it is not installed
+    pub formula_rec_predictor: Option<FormulaRecognitionPredictor>,
+    // optional, charts are kept as images when it is not installed
+    pub chart_rec_predictor: Option<ChartRecognitionPredictor>,
 }
 
 impl ModelContext {
@@ -29,6 +40,16 @@ impl ModelContext {
             settings.text_rec_model_path.as_str(),
             settings.text_charactor_list_path.as_str(),
         )?;
+        let mut lang_text_rec_predictors = HashMap::new();
+        for (language, model_path, character_path) in settings.lang_text_rec_models.iter() {
+            // language specific recognizers are optional, register the installed ones
+            if !Path::new(model_path).exists() {
+                continue;
+            }
+            let predictor =
+                TextRecognitionPredictor::try_new(model_path.as_str(), character_path.as_str())?;
+            lang_text_rec_predictors.insert(language.to_owned(), predictor);
+        }
         let layout_predictor = LayoutPredictor::try_new(settings.doc_layout_model_path.as_str())?;
         let table_cls_predictor =
             TableClsPredictor::try_new(settings.table_cls_model_path.as_str())?;
@@ -46,11 +67,28 @@ impl ModelContext {
         )?;
         let doc_text_ori_predictor =
             DocTextOriPredictor::try_new(&settings.doc_text_ori_model_path)?;
+        let formula_rec_predictor = if Path::new(&settings.formula_rec_model_path).exists() {
+            Some(FormulaRecognitionPredictor::try_new(
+                settings.formula_rec_model_path.as_str(),
+                settings.formula_rec_character_path.as_str(),
+            )?)
+        } else {
+            None
+        };
+        let chart_rec_predictor = if Path::new(&settings.chart_rec_model_path).exists() {
+            Some(ChartRecognitionPredictor::try_new(
+                settings.chart_rec_model_path.as_str(),
+                settings.chart_rec_character_path.as_str(),
+            )?)
+        } else {
+            None
+        };
 
         Ok(Self {
             settings,
             text_det_predictor,
             text_rec_predictor,
+            lang_text_rec_predictors,
             layout_predictor,
             table_cls_predictor,
             wired_table_cell_predictor,
@@ -58,6 +96,42 @@ impl ModelContext {
             wired_table_structure_predictor,
             wireless_table_structure_predictor,
             doc_text_ori_predictor,
+            formula_rec_predictor,
+            chart_rec_predictor,
         })
     }
+
+    // language of the default `text_rec_predictor`
+    pub fn default_language(&self) -> Language {
+        Language::Chinese
+    }
+
+    pub fn register_text_rec_predictor(
+        &mut self,
+        language: Language,
+        predictor: TextRecognitionPredictor,
+    ) {
+        self.lang_text_rec_predictors.insert(language, predictor);
+    }
+
+    pub fn registered_languages(&self) -> Vec<Language> {
+        let mut languages = vec![self.default_language()];
+        for language in self.lang_text_rec_predictors.keys() {
+            if !languages.contains(language) {
+                languages.push(language.to_owned());
+            }
+        }
+        languages
+    }
+
+    // the recognizer registered for `language`, falls back to the default recognizer
+    pub fn text_rec_predictor_for(
+        &self,
+        language: Language,
+    ) -> (Language, &TextRecognitionPredictor) {
+        match self.lang_text_rec_predictors.get(&language) {
+            Some(predictor) => (language, predictor),
+            None => (self.default_language(), &self.text_rec_predictor),
+        }
+    }
 }
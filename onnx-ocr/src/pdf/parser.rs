@@ -2,12 +2,54 @@ use anyhow::Result;
 use image::RgbImage;
 use pdfium_render::prelude::*;
 
+pub const RENDER_DPI: f32 = 150.0;
+
+// an entry of the pdf outline (bookmarks), top level entries have level 1
+#[derive(Debug, Clone)]
+pub struct OutlineEntry {
+    pub title: String,
+    pub level: u8,
+}
+
+pub fn pdf_outline(bytes: &[u8], password: Option<&str>) -> Result<Vec<OutlineEntry>> {
+    let pdfium = Pdfium::default();
+    let document = pdfium.load_pdf_from_byte_slice(bytes, password)?;
+    let mut outline = Vec::new();
+    collect_outline(document.bookmarks().root(), 1, &mut outline);
+    Ok(outline)
+}
+
+fn collect_outline(first: Option<PdfBookmark>, level: u8, outline: &mut Vec<OutlineEntry>) {
+    let mut bookmark = first;
+    while let Some(current) = bookmark {
+        if let Some(title) = current.title() {
+            outline.push(OutlineEntry { title, level });
+        }
+        collect_outline(current.first_child(), level.saturating_add(1), outline);
+        bookmark = current.next_sibling();
+    }
+}
+
 pub fn pdf_page_to_image(bytes: &[u8], page_num: u16, password: Option<&str>) -> Result<RgbImage> {
     let pdfium = Pdfium::default();
     let document = pdfium.load_pdf_from_byte_slice(bytes, password)?;
     let page = document.pages().get(page_num)?;
+    render_page(&page)
+}
+
+// every page of the document rendered at RENDER_DPI
+pub fn pdf_to_images(bytes: &[u8], password: Option<&str>) -> Result<Vec<RgbImage>> {
+    let pdfium = Pdfium::default();
+    let document = pdfium.load_pdf_from_byte_slice(bytes, password)?;
+    let mut images = Vec::new();
+    for page in document.pages().iter() {
+        images.push(render_page(&page)?);
+    }
+    Ok(images)
+}
 
-    let render_config = PdfRenderConfig::default().scale_page_width_by_factor(150.0 / 72.0);
+fn render_page(page: &PdfPage) -> Result<RgbImage> {
+    let render_config = PdfRenderConfig::default().scale_page_width_by_factor(RENDER_DPI / 72.0);
     let image = page
         .render_with_config(&render_config)?
         .as_image()
@@ -3,20 +3,32 @@ use std::collections::HashMap;
 use crate::common::quad::Quad;
 use crate::doc_layout::predictor::{LayoutLabel, LayoutResult};
 use crate::model_context::ModelContext;
+use crate::pdf::parser::OutlineEntry;
+use crate::pipeline::layout_parsing::heading::assign_heading_levels;
 use crate::pipeline::layout_parsing::layout_object::{LayoutBlock, LayoutRegion, TextSpan};
 use crate::pipeline::layout_parsing::util::caculate_overlap_ratio;
-use crate::pipeline::ocr::{self, OcrResultItem};
-use crate::pipeline::table::extract_table;
+use crate::pipeline::ocr::{self, LanguageRouting, OcrResultItem};
+use crate::pipeline::seal::recognize_seal;
+use crate::pipeline::table::{TableOptions, extract_table, table_from_rows};
 
 use anyhow::Result;
 use image::RgbImage;
 use image::imageops::{crop_imm, rotate90};
+use imageproc::point::Point;
+
+const PAGE_BATCH_SIZE: usize = 4;
 
 #[derive(Debug)]
 pub struct Document {
     pub objects: Vec<LayoutBlock>,
 }
 
+#[derive(Debug)]
+struct InlineFormula {
+    bbox: [f32; 4],
+    latex: String,
+}
+
 #[derive(Debug, Default)]
 struct ParsingInfo {
     block_to_ocr: HashMap<usize, Vec<usize>>,
@@ -27,31 +39,226 @@ struct ParsingInfo {
 
 pub struct LayoutParser<'a> {
     context: &'a ModelContext,
+    table_options: TableOptions,
+    outline: Vec<OutlineEntry>,
+    routing: LanguageRouting,
 }
 
 impl<'a> LayoutParser<'a> {
     pub fn new(context: &'a ModelContext) -> Self {
-        LayoutParser { context }
+        LayoutParser {
+            context,
+            table_options: TableOptions::default(),
+            outline: Vec::new(),
+            routing: LanguageRouting::Fixed(context.default_language()),
+        }
+    }
+
+    // resolution the page was rendered at, used to scale pixel tolerances
+    pub fn set_dpi(&mut self, dpi: f32) {
+        self.table_options.dpi = Some(dpi);
+    }
+
+    pub fn set_table_options(&mut self, options: TableOptions) {
+        self.table_options = options;
+    }
+
+    // how the page text is routed to the language recognizers, tables follow unless their
+    // options set a routing of their own
+    pub fn set_language_routing(&mut self, routing: LanguageRouting) {
+        self.routing = routing;
+    }
+
+    // outline of the pdf being parsed, used to give the section titles their heading level
+    pub fn set_outline(&mut self, outline: Vec<OutlineEntry>) {
+        self.outline = outline;
     }
 
     pub fn parse(&mut self, img: &RgbImage) -> Result<LayoutRegion> {
         let layout_predictor = &self.context.layout_predictor;
         let layout_result = layout_predictor.predict_image(&img)?;
-        let mut all_ocr_res = ocr::ocr(self.context, img)?;
+        let mut region = self.parse_with_layout(img, layout_result)?;
+        assign_heading_levels(std::slice::from_mut(&mut region), self.outline.as_slice());
+        Ok(region)
+    }
+
+    fn parse_with_layout(
+        &self,
+        img: &RgbImage,
+        layout_result: Vec<LayoutResult>,
+    ) -> Result<LayoutRegion> {
+        let mut all_ocr_res = ocr::ocr_with_routing(self.context, img, self.routing)?;
         // sandardized_layout
         let mut layout_result = remove_overlap_block(layout_result.as_slice(), 0.6);
+        let inline_formulas =
+            self.extract_inline_formulas(img, &mut layout_result, &mut all_ocr_res)?;
         let parsing_info = self.match_block_and_ocr(img, &mut layout_result, &mut all_ocr_res)?;
 
         let mut region = self.parsing_layout_region(
             img,
             layout_result.as_slice(),
             &all_ocr_res.as_slice(),
+            inline_formulas.as_slice(),
             &parsing_info,
         )?;
         region.sort_blocks();
         Ok(region)
     }
 
+    // formula boxes lying inside a text block are inline math, recognize them and cut their
+    // area out of the ocr lines so the text around them is read without the formula junk
+    fn extract_inline_formulas(
+        &self,
+        img: &RgbImage,
+        layout_res: &mut Vec<LayoutResult>,
+        ocr_res: &mut Vec<OcrResultItem>,
+    ) -> Result<Vec<InlineFormula>> {
+        let mut inline_ids = Vec::new();
+        for (obj_id, obj) in layout_res.iter().enumerate() {
+            if obj.label != LayoutLabel::Formula {
+                continue;
+            }
+            let area = obj.area();
+            let in_text = layout_res.iter().any(|other| {
+                is_text_block(&other.label)
+                    && other.area() > area
+                    && caculate_overlap_ratio(
+                        &obj.coordinate,
+                        area,
+                        &other.coordinate,
+                        other.area(),
+                    ) >= 0.8
+            });
+            if in_text {
+                inline_ids.push(obj_id);
+            }
+        }
+        if inline_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let boxes: Vec<[f32; 4]> = inline_ids
+            .iter()
+            .map(|obj_id| layout_res[obj_id.to_owned()].coordinate)
+            .collect();
+        let mut obj_id = 0;
+        layout_res.retain(|_| {
+            let keep = !inline_ids.contains(&obj_id);
+            obj_id += 1;
+            keep
+        });
+        // without the formula model inline math stays in the ocr text of its block
+        let Some(predictor) = &self.context.formula_rec_predictor else {
+            return Ok(Vec::new());
+        };
+
+        // a box on the image edge may crop to nothing, there is no formula to read in it
+        let (boxes, formula_imgs): (Vec<[f32; 4]>, Vec<RgbImage>) = boxes
+            .into_iter()
+            .map(|bbox| (bbox, crop_sub_img(&bbox, img)))
+            .filter(|(_, crop)| crop.width() > 0 && crop.height() > 0)
+            .unzip();
+        if boxes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let latexs = predictor.predict(formula_imgs)?;
+        self.split_ocr_by_formulas(img, boxes.as_slice(), ocr_res)?;
+
+        let inline_formulas = boxes
+            .into_iter()
+            .zip(latexs)
+            .filter(|(_, latex)| !latex.is_empty())
+            .map(|(bbox, latex)| InlineFormula { bbox, latex })
+            .collect();
+        Ok(inline_formulas)
+    }
+
+    // an ocr line crossing inline formulas is split into the text segments left between them,
+    // segments are recognized again and the covered part is dropped
+    fn split_ocr_by_formulas(
+        &self,
+        img: &RgbImage,
+        formulas: &[[f32; 4]],
+        ocr_res: &mut Vec<OcrResultItem>,
+    ) -> Result<()> {
+        let mut res = Vec::with_capacity(ocr_res.len());
+        for item in std::mem::take(ocr_res) {
+            let [x1, y1, x2, y2] = item.bbox;
+            let height = y2 - y1;
+            // vertical lines are kept as they are
+            if height > x2 - x1 {
+                res.push(item);
+                continue;
+            }
+            let mut covered: Vec<(f32, f32)> = formulas
+                .iter()
+                .filter(|f| f[0] < x2 && f[2] > x1 && y2.min(f[3]) - y1.max(f[1]) >= height * 0.5)
+                .map(|f| (f[0].max(x1), f[2].min(x2)))
+                .collect();
+            if covered.is_empty() {
+                res.push(item);
+                continue;
+            }
+            covered.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut segments = Vec::new();
+            let mut start = x1;
+            for (cx1, cx2) in covered {
+                if cx1 - start > height * 0.5 {
+                    segments.push([start, y1, cx1, y2]);
+                }
+                start = start.max(cx2);
+            }
+            if x2 - start > height * 0.5 {
+                segments.push([start, y1, x2, y2]);
+            }
+            if segments.is_empty() {
+                continue;
+            }
+            let sub_imgs = segments
+                .iter()
+                .map(|bbox| crop_sub_img(bbox, img))
+                .collect();
+            // the segments are read in the language the whole line was routed to
+            let texts = ocr::recognize(
+                self.context,
+                sub_imgs,
+                LanguageRouting::Fixed(item.language),
+            )?;
+            for (bbox, text) in segments.into_iter().zip(texts) {
+                let content = text.content.trim();
+                if content.is_empty() || text.score < 0.5 {
+                    continue;
+                }
+                let polys = Quad::new_from_bbox(&bbox);
+                res.push(OcrResultItem::new(
+                    polys,
+                    content.to_string(),
+                    bbox,
+                    text.language,
+                    text.score,
+                ));
+            }
+        }
+        *ocr_res = res;
+        Ok(())
+    }
+
+    // parse the pages of one document, tables broken by page breaks are merged
+    pub fn parse_pages(&mut self, imgs: &[RgbImage]) -> Result<Vec<LayoutRegion>> {
+        let mut pages = Vec::with_capacity(imgs.len());
+        // the layout model runs on a few pages at once
+        for batch in imgs.chunks(PAGE_BATCH_SIZE) {
+            let layouts = self.context.layout_predictor.predict_batch(batch)?;
+            for (img, layout_result) in batch.iter().zip(layouts) {
+                pages.push(self.parse_with_layout(img, layout_result)?);
+            }
+        }
+        merge_cross_page_tables(pages.as_mut_slice());
+        merge_continued_paragraphs(pages.as_mut_slice());
+        // heading levels are made consistent over the whole document
+        assign_heading_levels(pages.as_mut_slice(), self.outline.as_slice());
+        Ok(pages)
+    }
+
     fn match_block_and_ocr(
         &self,
         img: &RgbImage,
@@ -125,16 +332,27 @@ impl<'a> LayoutParser<'a> {
                             if sub_img.width() < sub_img.height() {
                                 sub_img = rotate90(&sub_img);
                             }
-                            let text_rec_res =
-                                self.context.text_rec_predictor.predict(vec![sub_img])?;
+                            let language = ocr_res[ocr_id.to_owned()].language;
+                            let text_rec_res = ocr::recognize(
+                                self.context,
+                                vec![sub_img],
+                                LanguageRouting::Fixed(language),
+                            )?;
                             let text = &text_rec_res[0];
-                            if text.1 >= 0.5 {
+                            if text.score >= 0.5 {
                                 if match_num == 0 {
-                                    ocr_res[ocr_id.to_owned()].content = text.0.to_owned();
+                                    ocr_res[ocr_id.to_owned()].content = text.content.to_owned();
+                                    ocr_res[ocr_id.to_owned()].score = text.score;
                                 } else {
-                                    let content = text.0.to_string();
+                                    let content = text.content.to_string();
                                     let polys = Quad::new_from_bbox(&bbox);
-                                    let new_ocr = OcrResultItem::new(polys, content, bbox);
+                                    let new_ocr = OcrResultItem::new(
+                                        polys,
+                                        content,
+                                        bbox,
+                                        text.language,
+                                        text.score,
+                                    );
                                     ocr_res.push(new_ocr);
                                     block_to_ocr
                                         .get_mut(block_id)
@@ -170,6 +388,7 @@ impl<'a> LayoutParser<'a> {
         img: &RgbImage,
         layout_res: &[LayoutResult],
         ocr_res: &[OcrResultItem],
+        inline_formulas: &[InlineFormula],
         parsing_info: &ParsingInfo,
     ) -> Result<LayoutRegion> {
         let mut doc_objects = Vec::new();
@@ -189,22 +408,63 @@ impl<'a> LayoutParser<'a> {
                 | LayoutLabel::Content
                 | LayoutLabel::Algorithm
                 | LayoutLabel::ReferenceContent
+                | LayoutLabel::FormulaNumber
                 | LayoutLabel::AsideText => {
+                    let mut spans: Vec<TextSpan> = Vec::new();
                     if let Some(ocr_ids) = parsing_info.block_to_ocr.get(&obj_id) {
-                        let items: Vec<OcrResultItem> = ocr_ids
-                            .iter()
-                            .map(|ocr_id| ocr_res.get(ocr_id.to_owned()).unwrap().to_owned())
-                            .collect();
-                        block.update_text_content(items.as_slice());
+                        spans.extend(ocr_ids.iter().map(|ocr_id| {
+                            TextSpan::new_from_ocr(ocr_res.get(ocr_id.to_owned()).unwrap())
+                        }));
+                    }
+                    for formula in inline_formulas.iter() {
+                        let area = caculate_rect_area(&formula.bbox);
+                        let ratio = caculate_overlap_ratio(
+                            &formula.bbox,
+                            area,
+                            &obj.coordinate,
+                            obj.area(),
+                        );
+                        if ratio >= 0.8 {
+                            spans.push(TextSpan::new_formula(formula.bbox, &formula.latex));
+                        }
+                    }
+                    if !spans.is_empty() {
+                        block.update_text_spans(spans.as_slice());
                     }
                 }
                 LayoutLabel::Chart => {
-                    println!("chart")
+                    let img = crop_sub_img(&obj.coordinate, img);
+                    if let Some(predictor) = &self.context.chart_rec_predictor {
+                        let rows = predictor.predict(vec![img.clone()])?;
+                        if let Some(rows) = rows.into_iter().next()
+                            && !rows.is_empty()
+                        {
+                            block.set_chart_table(table_from_rows(rows.as_slice(), 1));
+                        }
+                    }
+                    block.set_image(img);
                 }
 
-                LayoutLabel::Formula | LayoutLabel::FormulaNumber => {
-                    println!("formular")
-                }
+                LayoutLabel::Formula => match &self.context.formula_rec_predictor {
+                    Some(predictor) => {
+                        let formula_img = crop_sub_img(&obj.coordinate, img);
+                        if formula_img.width() > 0 && formula_img.height() > 0 {
+                            let latex = predictor.predict(vec![formula_img])?;
+                            block.set_formula_content(latex.into_iter().next().unwrap_or_default());
+                        }
+                    }
+                    None => {
+                        // no formula model installed, keep the ocr text read in the formula area
+                        block = LayoutBlock::new(LayoutLabel::Text, obj.coordinate);
+                        let spans: Vec<TextSpan> = get_sub_region_ocr_res(ocr_res, &[obj])
+                            .into_iter()
+                            .map(|ocr_id| TextSpan::new_from_ocr(&ocr_res[ocr_id]))
+                            .collect();
+                        if !spans.is_empty() {
+                            block.update_text_spans(spans.as_slice());
+                        }
+                    }
+                },
 
                 LayoutLabel::Image => {
                     let img = crop_sub_img(&obj.coordinate, img);
@@ -222,29 +482,200 @@ impl<'a> LayoutParser<'a> {
                                 item.bbox[1] = (item.bbox[1] - table_y).max(0.0);
                                 item.bbox[2] = (item.bbox[2] - table_x).max(0.0);
                                 item.bbox[3] = (item.bbox[3] - table_y).max(0.0);
+                                item.polys = item
+                                    .polys
+                                    .map_points(|p| Point::new(p.x - table_x, p.y - table_y));
                                 item
                             })
                             .collect();
                         println!("table ocr items: {:?}", ocr_items.len());
                         // todo
                         let table_img = crop_sub_img(&obj.coordinate, img);
-                        let table = extract_table(self.context, &table_img, ocr_items.as_slice())?;
-                        block.set_table_content(table.to_html());
+                        let options = TableOptions {
+                            routing: self.table_options.routing.or(Some(self.routing)),
+                            ..self.table_options
+                        };
+                        let mut table = extract_table(
+                            self.context,
+                            &table_img,
+                            ocr_items.as_slice(),
+                            &options,
+                        )?;
+                        table.translate(table_x, table_y);
+                        block.set_table(table);
                     } else {
                         println!("table ocr is None");
                     }
                 }
                 LayoutLabel::Seal => {
-                    println!("ignore seal");
+                    let img = crop_sub_img(&obj.coordinate, img);
+                    let seal = recognize_seal(self.context, &img)?;
+                    block.set_seal_text(&seal);
+                    block.set_image(img);
                 }
             }
             doc_objects.push(block);
         }
+        attach_formula_numbers(&mut doc_objects);
+        attach_captions(&mut doc_objects);
         let region = LayoutRegion::new(doc_objects, parsing_info.region_box);
         Ok(region)
     }
 }
 
+// a table ending a page is continued by a table starting the next one when their columns match,
+// the continuation is merged into the first table and removed from its page
+pub fn merge_cross_page_tables(pages: &mut [LayoutRegion]) {
+    let mut open: Option<(usize, usize)> = None;
+    for page_id in 0..pages.len() {
+        if let Some((open_page, open_block)) = open
+            && let Some(first) = first_content_block(pages[page_id].blocks())
+            && ends_near_bottom(&pages[open_page], open_block)
+            && starts_near_top(&pages[page_id], first)
+            && is_continued_table(
+                &pages[open_page].blocks()[open_block],
+                &pages[page_id].blocks()[first],
+            )
+        {
+            let last = last_content_block(pages[page_id].blocks());
+            let block = pages[page_id].blocks_mut().remove(first);
+            pages[page_id].renumber_blocks();
+            if let Some(table) = block.table {
+                let target = &mut pages[open_page].blocks_mut()[open_block];
+                if let Some(mut merged) = target.table.take() {
+                    merged.merge(table);
+                    target.set_table(merged);
+                }
+            }
+            // the table fills the whole page and may go on to the next one
+            if last == Some(first) {
+                continue;
+            }
+        }
+        open = last_content_block(pages[page_id].blocks())
+            .filter(|id| pages[page_id].blocks()[*id].table.is_some())
+            .map(|id| (page_id, id));
+    }
+}
+
+// a paragraph broken at the end of a column or page is merged into its first part, figures and
+// tables floating in between are skipped
+pub fn merge_continued_paragraphs(pages: &mut [LayoutRegion]) {
+    let mut open: Option<(usize, usize)> = None;
+    for page_id in 0..pages.len() {
+        let mut block_id = 0;
+        let mut merged = false;
+        while block_id < pages[page_id].blocks().len() {
+            let block = &pages[page_id].blocks()[block_id];
+            if is_page_furniture(&block.label) || is_float(&block.label) {
+                block_id += 1;
+                continue;
+            }
+            if let Some((open_page, open_block)) = open {
+                let prev = &pages[open_page].blocks()[open_block];
+                // inside a column the layout model already split the paragraphs, full width
+                // blocks (no column) only continue on the next page
+                let other_column = match (prev.column, block.column) {
+                    _ if open_page != page_id => true,
+                    (Some(prev_column), Some(column)) => prev_column != column,
+                    _ => false,
+                };
+                if other_column
+                    && ends_column(&pages[open_page], open_block)
+                    && prev.is_continued_by(block)
+                {
+                    let next = pages[page_id].blocks_mut().remove(block_id);
+                    pages[open_page].blocks_mut()[open_block].append_continuation(next, page_id);
+                    merged = true;
+                    continue;
+                }
+            }
+            open = Some((page_id, block_id));
+            block_id += 1;
+        }
+        if merged {
+            pages[page_id].renumber_blocks();
+        }
+    }
+}
+
+fn is_float(label: &LayoutLabel) -> bool {
+    matches!(
+        label,
+        LayoutLabel::Image
+            | LayoutLabel::Table
+            | LayoutLabel::Chart
+            | LayoutLabel::Seal
+            | LayoutLabel::FigureTitle
+    )
+}
+
+fn is_continued_table(prev: &LayoutBlock, next: &LayoutBlock) -> bool {
+    if prev.label != LayoutLabel::Table || next.label != LayoutLabel::Table {
+        return false;
+    }
+    match (&prev.table, &next.table) {
+        (Some(prev), Some(next)) => prev.is_continued_by(next),
+        _ => false,
+    }
+}
+
+// a table or paragraph broken by the page break runs down to the bottom of the page content and
+// goes on at its top, one ending mid-page is complete even if the next block looks like its end
+const PAGE_EDGE_RATIO: f32 = 0.15;
+
+fn ends_near_bottom(page: &LayoutRegion, block_id: usize) -> bool {
+    let (top, bottom) = vertical_extent(page.blocks());
+    bottom - page.blocks()[block_id].bbox[3] <= (bottom - top) * PAGE_EDGE_RATIO
+}
+
+fn starts_near_top(page: &LayoutRegion, block_id: usize) -> bool {
+    let (top, bottom) = vertical_extent(page.blocks());
+    page.blocks()[block_id].bbox[1] - top <= (bottom - top) * PAGE_EDGE_RATIO
+}
+
+// the block is the last content of its column, nothing but page furniture or a thin strip
+// lies below it
+fn ends_column(page: &LayoutRegion, block_id: usize) -> bool {
+    let (top, bottom) = vertical_extent(page.blocks());
+    let block = &page.blocks()[block_id];
+    let column_bottom = page
+        .blocks()
+        .iter()
+        .filter(|b| !is_page_furniture(&b.label))
+        .filter(|b| block.column.is_none() || b.column == block.column)
+        .map(|b| b.bbox[3])
+        .fold(block.bbox[3], f32::max);
+    column_bottom - block.bbox[3] <= (bottom - top) * PAGE_EDGE_RATIO
+}
+
+// top and bottom of everything laid out on the page, headers and footers included
+fn vertical_extent(blocks: &[LayoutBlock]) -> (f32, f32) {
+    let top = blocks.iter().map(|b| b.bbox[1]).fold(f32::MAX, f32::min);
+    let bottom = blocks.iter().map(|b| b.bbox[3]).fold(f32::MIN, f32::max);
+    (top, bottom)
+}
+
+// page furniture is skipped when looking for the blocks around a page break
+fn is_page_furniture(label: &LayoutLabel) -> bool {
+    matches!(
+        label,
+        LayoutLabel::Header
+            | LayoutLabel::Footer
+            | LayoutLabel::Number
+            | LayoutLabel::Footnote
+            | LayoutLabel::AsideText
+    )
+}
+
+fn first_content_block(blocks: &[LayoutBlock]) -> Option<usize> {
+    blocks.iter().position(|b| !is_page_furniture(&b.label))
+}
+
+fn last_content_block(blocks: &[LayoutBlock]) -> Option<usize> {
+    blocks.iter().rposition(|b| !is_page_furniture(&b.label))
+}
+
 fn remove_overlap_block(layout_res: &[LayoutResult], threshod: f32) -> Vec<LayoutResult> {
     let n = layout_res.len();
     let mut removed_boxes = Vec::new();
@@ -268,6 +699,12 @@ fn remove_overlap_block(layout_res: &[LayoutResult], threshod: f32) -> Vec<Layou
                 other_area,
             );
             if overlap_ratio > threshod {
+                // inline formulas are kept, they are cut out of the text block later
+                if is_inline_formula_pair(&current.label, &other.label)
+                    || is_inline_formula_pair(&other.label, &current.label)
+                {
+                    continue;
+                }
                 if current.label == LayoutLabel::Image && other.label != LayoutLabel::Image {
                     removed_boxes.push(i);
                 } else if current.label != LayoutLabel::Image && other.label == LayoutLabel::Image {
@@ -292,6 +729,168 @@ fn remove_overlap_block(layout_res: &[LayoutResult], threshod: f32) -> Vec<Layou
     res
 }
 
+// attach every formula number to the closest formula on the same baseline, attached numbers
+// are removed from the blocks and rendered as the formula `\tag{}`
+fn attach_formula_numbers(blocks: &mut Vec<LayoutBlock>) {
+    let mut pairs = Vec::new();
+    for (number_id, number) in blocks.iter().enumerate() {
+        if number.label != LayoutLabel::FormulaNumber || number.content.trim().is_empty() {
+            continue;
+        }
+        let [nx1, ny1, nx2, ny2] = number.bbox;
+        let number_height = ny2 - ny1;
+        if number_height <= 0.0 {
+            continue;
+        }
+        for (formula_id, formula) in blocks.iter().enumerate() {
+            if formula.label != LayoutLabel::Formula {
+                continue;
+            }
+            let [fx1, fy1, fx2, fy2] = formula.bbox;
+            let overlap = ny2.min(fy2) - ny1.max(fy1);
+            if overlap < number_height * 0.5 {
+                continue;
+            }
+            let gap = if nx1 >= fx2 {
+                nx1 - fx2
+            } else if fx1 >= nx2 {
+                fx1 - nx2
+            } else {
+                0.0
+            };
+            pairs.push((gap, number_id, formula_id));
+        }
+    }
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut attached_numbers = Vec::new();
+    let mut numbered_formulas = Vec::new();
+    for (_, number_id, formula_id) in pairs {
+        if attached_numbers.contains(&number_id) || numbered_formulas.contains(&formula_id) {
+            continue;
+        }
+        let number = blocks[number_id].content.to_owned();
+        blocks[formula_id].set_formula_number(&number);
+        attached_numbers.push(number_id);
+        numbered_formulas.push(formula_id);
+    }
+    let mut block_id = 0;
+    blocks.retain(|_| {
+        let keep = !attached_numbers.contains(&block_id);
+        block_id += 1;
+        keep
+    });
+}
+
+// attach every figure title to the closest figure, table or chart above, below or beside it,
+// attached titles are removed from the blocks and kept as the block caption
+fn attach_captions(blocks: &mut Vec<LayoutBlock>) {
+    let mut pairs = Vec::new();
+    for (caption_id, caption) in blocks.iter().enumerate() {
+        if caption.label != LayoutLabel::FigureTitle || caption.content.trim().is_empty() {
+            continue;
+        }
+        let [cx1, cy1, cx2, cy2] = caption.bbox;
+        let caption_height = cy2 - cy1;
+        if caption_height <= 0.0 {
+            continue;
+        }
+        let names_table = names_table(caption.content.as_str());
+        for (target_id, target) in blocks.iter().enumerate() {
+            if !is_captioned(&target.label) {
+                continue;
+            }
+            // "Table 2" does not describe a figure and "Fig. 3" does not describe a table
+            if names_table.is_some_and(|is_table| is_table != (target.label == LayoutLabel::Table))
+            {
+                continue;
+            }
+            let [tx1, ty1, tx2, ty2] = target.bbox;
+            let x_overlap = cx2.min(tx2) - cx1.max(tx1);
+            let y_overlap = cy2.min(ty2) - cy1.max(ty1);
+            let gap = if x_overlap > (cx2 - cx1).min(tx2 - tx1) * 0.3 {
+                let above = cy2 <= (ty1 + ty2) / 2.0;
+                // tables are captioned above, figures below
+                let usual_side = above == (target.label == LayoutLabel::Table);
+                let gap = (ty1 - cy2).max(cy1 - ty2).max(0.0);
+                if usual_side {
+                    gap
+                } else {
+                    gap + caption_height * 0.5
+                }
+            } else if y_overlap > caption_height * 0.5 {
+                (tx1 - cx2).max(cx1 - tx2).max(0.0)
+            } else {
+                continue;
+            };
+            if gap > caption_height * 4.0 {
+                continue;
+            }
+            pairs.push((gap, caption_id, target_id));
+        }
+    }
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut attached_captions = Vec::new();
+    let mut captioned_targets = Vec::new();
+    for (_, caption_id, target_id) in pairs {
+        if attached_captions.contains(&caption_id) || captioned_targets.contains(&target_id) {
+            continue;
+        }
+        let caption = blocks[caption_id].content.to_owned();
+        blocks[target_id].set_caption(&caption);
+        attached_captions.push(caption_id);
+        captioned_targets.push(target_id);
+    }
+    let mut block_id = 0;
+    blocks.retain(|_| {
+        let keep = !attached_captions.contains(&block_id);
+        block_id += 1;
+        keep
+    });
+}
+
+// whether the caption names a table or a figure, none when it has no such prefix
+fn names_table(caption: &str) -> Option<bool> {
+    let caption = caption.trim_start().to_lowercase();
+    if caption.starts_with("tab") || caption.starts_with('表') {
+        Some(true)
+    } else if caption.starts_with("fig")
+        || caption.starts_with("chart")
+        || caption.starts_with('图')
+    {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn is_captioned(label: &LayoutLabel) -> bool {
+    matches!(
+        label,
+        LayoutLabel::Image | LayoutLabel::Table | LayoutLabel::Chart
+    )
+}
+
+fn is_text_block(label: &LayoutLabel) -> bool {
+    matches!(
+        label,
+        LayoutLabel::Text
+            | LayoutLabel::Abstract
+            | LayoutLabel::Content
+            | LayoutLabel::Reference
+            | LayoutLabel::ReferenceContent
+            | LayoutLabel::Footnote
+            | LayoutLabel::ParaGraphTitle
+            | LayoutLabel::DocTitle
+            | LayoutLabel::FigureTitle
+            | LayoutLabel::AsideText
+            | LayoutLabel::Algorithm
+    )
+}
+
+fn is_inline_formula_pair(formula: &LayoutLabel, text: &LayoutLabel) -> bool {
+    *formula == LayoutLabel::Formula && is_text_block(text)
+}
+
 fn update_region_box(region: &mut [f32; 4], rect: &[f32; 4]) {
     let [rx1, ry1, rx2, ry2] = rect;
     region[0] = region[0].min(*rx1);
@@ -348,3 +947,54 @@ fn crop_sub_img(bbox: &[f32; 4], img: &RgbImage) -> RgbImage {
     let sub_img = crop_imm(img, x, y, width, height).to_image();
     sub_img
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_block(bbox: [f32; 4], column: Option<usize>, content: &str) -> LayoutBlock {
+        let mut block = LayoutBlock::new(LayoutLabel::Text, bbox);
+        block.column = column;
+        block.content = content.to_string();
+        block
+    }
+
+    #[test]
+    fn paragraph_continues_in_the_next_column() {
+        let blocks = vec![
+            text_block([0.0, 0.0, 90.0, 1000.0], Some(0), "the paragraph goes"),
+            text_block([110.0, 0.0, 200.0, 300.0], Some(1), "on here."),
+        ];
+        let mut pages = vec![LayoutRegion::new(blocks, [0.0; 4])];
+        merge_continued_paragraphs(pages.as_mut_slice());
+        assert_eq!(pages[0].blocks().len(), 1);
+        assert_eq!(pages[0].blocks()[0].content, "the paragraph goes on here.");
+    }
+
+    #[test]
+    fn full_width_paragraph_is_not_merged_into_a_column() {
+        let blocks = vec![
+            text_block([0.0, 0.0, 200.0, 100.0], None, "a full width abstract"),
+            text_block([0.0, 120.0, 90.0, 1000.0], Some(0), "starting lower case."),
+            text_block([110.0, 120.0, 200.0, 1000.0], Some(1), "second column."),
+        ];
+        let mut pages = vec![LayoutRegion::new(blocks, [0.0; 4])];
+        merge_continued_paragraphs(pages.as_mut_slice());
+        assert_eq!(pages[0].blocks().len(), 3);
+    }
+
+    #[test]
+    fn paragraph_ending_mid_column_is_not_continued() {
+        let blocks = vec![
+            text_block([0.0, 0.0, 90.0, 300.0], Some(0), "a caption like line"),
+            LayoutBlock {
+                column: Some(0),
+                ..LayoutBlock::new(LayoutLabel::Image, [0.0, 320.0, 90.0, 1000.0])
+            },
+            text_block([110.0, 0.0, 200.0, 300.0], Some(1), "more text."),
+        ];
+        let mut pages = vec![LayoutRegion::new(blocks, [0.0; 4])];
+        merge_continued_paragraphs(pages.as_mut_slice());
+        assert_eq!(pages[0].blocks().len(), 3);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    doc_layout::predictor::LayoutLabel, pdf::parser::OutlineEntry,
+    pipeline::layout_parsing::layout_object::LayoutRegion,
+};
+
+// heading levels of the titles of a document. the document title is H1 and the section titles
+// below it take their depth from the pdf outline, from their numbering ("1.2" is one level
+// deeper than "1.") or else from their glyph height. a heading is at most one level deeper than
+// the heading before it so the levels form a tree
+pub fn assign_heading_levels(pages: &mut [LayoutRegion], outline: &[OutlineEntry]) {
+    let mut titles = Vec::new();
+    for (page_id, page) in pages.iter().enumerate() {
+        for (block_id, block) in page.blocks().iter().enumerate() {
+            if matches!(
+                block.label,
+                LayoutLabel::DocTitle | LayoutLabel::ParaGraphTitle
+            ) {
+                titles.push((page_id, block_id));
+            }
+        }
+    }
+    let has_doc_title = titles
+        .iter()
+        .any(|(p, b)| pages[*p].blocks()[*b].label == LayoutLabel::DocTitle);
+    let base = if has_doc_title { 1 } else { 0 };
+
+    // section depth from the outline or the numbering, heights of the resolved titles are kept to
+    // place the unnumbered ones
+    let mut depths: Vec<Option<u8>> = Vec::with_capacity(titles.len());
+    let mut known_heights: Vec<(f32, u8)> = Vec::new();
+    for (page_id, block_id) in titles.iter() {
+        let block = &pages[*page_id].blocks()[*block_id];
+        if block.label == LayoutLabel::DocTitle {
+            depths.push(Some(0));
+            continue;
+        }
+        let depth = outline_depth(block.content.as_str(), outline)
+            .or_else(|| numbering_depth(block.content.as_str()));
+        if let Some(depth) = depth {
+            known_heights.push((block.text_line_height, depth));
+        }
+        depths.push(depth);
+    }
+    let mut unknown_heights: Vec<f32> = titles
+        .iter()
+        .zip(depths.iter())
+        .filter(|(_, depth)| depth.is_none())
+        .map(|((p, b), _)| pages[*p].blocks()[*b].text_line_height)
+        .collect();
+    unknown_heights.sort_by(|a, b| b.total_cmp(a));
+    let height_ranks = height_clusters(unknown_heights.as_slice());
+
+    let mut previous = 0;
+    for ((page_id, block_id), depth) in titles.into_iter().zip(depths) {
+        let block = &mut pages[page_id].blocks_mut()[block_id];
+        let level = match depth {
+            Some(0) => 1,
+            Some(depth) => base + depth,
+            None => {
+                let height = block.text_line_height;
+                let similar = known_heights
+                    .iter()
+                    .filter(|(h, _)| (h - height).abs() <= height.max(*h) * 0.1)
+                    .min_by(|a, b| (a.0 - height).abs().total_cmp(&(b.0 - height).abs()));
+                let depth = match similar {
+                    Some((_, depth)) => *depth,
+                    None => height_rank(height, height_ranks.as_slice()) + 1,
+                };
+                base + depth
+            }
+        };
+        let level = level.min(previous + 1).clamp(1, 6);
+        block.heading_level = Some(level);
+        previous = level;
+    }
+}
+
+// title heights from large to small grouped when within 10% of each other, one height per group
+fn height_clusters(sorted_heights: &[f32]) -> Vec<f32> {
+    let mut clusters: Vec<f32> = Vec::new();
+    for height in sorted_heights.iter() {
+        match clusters.last() {
+            Some(last) if last - height <= last * 0.1 => {}
+            _ => clusters.push(*height),
+        }
+    }
+    clusters
+}
+
+fn height_rank(height: f32, clusters: &[f32]) -> u8 {
+    clusters
+        .iter()
+        .position(|c| c - height <= c * 0.1)
+        .unwrap_or(clusters.len().saturating_sub(1)) as u8
+}
+
+fn outline_depth(title: &str, outline: &[OutlineEntry]) -> Option<u8> {
+    let title = normalize_title(title);
+    if title.is_empty() {
+        return None;
+    }
+    outline
+        .iter()
+        .find(|entry| {
+            let entry_title = normalize_title(entry.title.as_str());
+            entry_title == title
+                || (entry_title.chars().count() >= 4
+                    && title.chars().count() >= 4
+                    && (entry_title.contains(title.as_str())
+                        || title.contains(entry_title.as_str())))
+        })
+        .map(|entry| entry.level)
+}
+
+// lowercase letters and digits only, ocr and outline titles differ in spacing and punctuation
+fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+const CJK_NUMERALS: &str = "零一二三四五六七八九十百千两〇";
+
+// section depth from the numbering of a title: "1." and "第一章" are top level sections,
+// "1.2" and "第一节" one deeper and so on
+pub fn numbering_depth(title: &str) -> Option<u8> {
+    let title = title.trim();
+    let chars: Vec<char> = title.chars().collect();
+
+    if chars.first() == Some(&'第') {
+        let end = chars[1..]
+            .iter()
+            .position(|c| !(c.is_ascii_digit() || CJK_NUMERALS.contains(*c)))
+            .map(|p| p + 1)?;
+        if end > 1 {
+            return match chars[end] {
+                '章' | '篇' | '部' => Some(1),
+                '节' => Some(2),
+                '条' => Some(3),
+                _ => None,
+            };
+        }
+    }
+    // "一、" and "（一）"
+    let numeral_end = chars
+        .iter()
+        .position(|c| !CJK_NUMERALS.contains(*c))
+        .unwrap_or(chars.len());
+    if numeral_end > 0 && chars.get(numeral_end) == Some(&'、') {
+        return Some(1);
+    }
+    if matches!(chars.first(), Some('（' | '(')) {
+        let inner_end = chars[1..]
+            .iter()
+            .position(|c| matches!(c, '）' | ')'))
+            .map(|p| p + 1)?;
+        let inner = &chars[1..inner_end];
+        if !inner.is_empty() && inner.iter().all(|c| CJK_NUMERALS.contains(*c)) {
+            return Some(2);
+        }
+        if !inner.is_empty() && inner.iter().all(|c| c.is_ascii_digit()) {
+            return Some(3);
+        }
+        return None;
+    }
+
+    let lower = title.to_lowercase();
+    for word in ["chapter ", "part "] {
+        if lower.starts_with(word) {
+            return Some(1);
+        }
+    }
+
+    // "1", "1.", "1.2", "1.2.3 " followed by the title text
+    let mut groups = 0;
+    let mut i = 0;
+    loop {
+        let start = i;
+        while i < chars.len() && chars[i].is_ascii_digit() {
+            i += 1;
+        }
+        let digits = i - start;
+        // a year or an amount, not a section number
+        if digits == 0 || digits > 3 {
+            break;
+        }
+        groups += 1;
+        if i < chars.len()
+            && chars[i] == '.'
+            && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())
+        {
+            i += 1;
+            continue;
+        }
+        break;
+    }
+    if groups > 0 {
+        let rest = &chars[i..];
+        let separated = match rest.first() {
+            // a bare "1.2" title, a lone "3" is more likely a page or list number
+            None => groups > 1,
+            Some('.' | '、' | ')' | '）') => true,
+            Some(c) => c.is_whitespace() || (groups > 1 && c.is_alphabetic()),
+        };
+        if separated {
+            return Some(groups.min(6) as u8);
+        }
+        return None;
+    }
+
+    // "IV. Results" and "B. Setup"
+    let word_end = chars.iter().position(|c| *c == '.').unwrap_or(0);
+    if word_end > 0 && chars.get(word_end + 1).is_some_and(|c| c.is_whitespace()) {
+        let word = &chars[..word_end];
+        // a single letter is a lettered section unless it reads as the first roman numerals,
+        // "C." and "L." are sections C and L rather than 100 and 50
+        let roman = word.iter().all(|c| "IVXLC".contains(*c))
+            && (word.len() > 1 || matches!(word[0], 'I' | 'V' | 'X'));
+        if roman {
+            return Some(1);
+        }
+        if word.len() == 1 && word[0].is_ascii_uppercase() {
+            return Some(2);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arabic_numbering() {
+        assert_eq!(numbering_depth("1. Introduction"), Some(1));
+        assert_eq!(numbering_depth("1.2"), Some(2));
+        assert_eq!(numbering_depth("1.2.3 Training"), Some(3));
+        assert_eq!(numbering_depth("2020 Report"), None);
+        assert_eq!(numbering_depth("3.5% growth"), None);
+    }
+
+    #[test]
+    fn cjk_numbering() {
+        assert_eq!(numbering_depth("第一章 总则"), Some(1));
+        assert_eq!(numbering_depth("第二节"), Some(2));
+        assert_eq!(numbering_depth("一、概述"), Some(1));
+        assert_eq!(numbering_depth("（一）"), Some(2));
+        assert_eq!(numbering_depth("(3) 附则"), Some(3));
+    }
+
+    #[test]
+    fn roman_and_lettered_numbering() {
+        assert_eq!(numbering_depth("IV. Results"), Some(1));
+        assert_eq!(numbering_depth("I. Introduction"), Some(1));
+        assert_eq!(numbering_depth("A. Setup"), Some(2));
+        assert_eq!(numbering_depth("C. Datasets"), Some(2));
+        assert_eq!(numbering_depth("L. Limitations"), Some(2));
+        assert_eq!(numbering_depth("Results"), None);
+    }
+}
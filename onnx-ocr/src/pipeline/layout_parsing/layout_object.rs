@@ -1,8 +1,17 @@
 use anyhow::Result;
 use image::RgbImage;
-use std::cmp::Ordering;
+use itertools::Itertools;
+use std::{collections::HashMap, path::Path};
 
-use crate::{doc_layout::predictor::LayoutLabel, pipeline::ocr::OcrResultItem};
+use crate::{
+    doc_layout::predictor::LayoutLabel,
+    pipeline::{
+        layout_parsing::reading_order::{column_of, detect_columns, reading_order},
+        ocr::OcrResultItem,
+        seal::SealText,
+        table::{TableFormat, TableResult},
+    },
+};
 
 #[derive(Debug, Clone)]
 pub struct TextSpan {
@@ -17,6 +26,11 @@ impl TextSpan {
         let text = item.content.to_owned();
         Self { label, bbox, text }
     }
+    pub fn new_formula(bbox: [f32; 4], latex: &str) -> Self {
+        let label = LayoutLabel::Formula;
+        let text = format!("${}$", latex);
+        Self { label, bbox, text }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -69,7 +83,7 @@ impl TextLine {
                     return false;
                 }
                 let p = (self.region_box[3] - self.region_box[1]).min(span.bbox[3] - span.bbox[1]);
-                let ratio = end - start / p;
+                let ratio = (end - start) / p;
                 ratio > 0.8
             }
             Direction::Vertical => {
@@ -79,7 +93,7 @@ impl TextLine {
                     return false;
                 }
                 let p = (self.region_box[2] - self.region_box[0]).min(span.bbox[2] - span.bbox[0]);
-                let ratio = end - start / p;
+                let ratio = (end - start) / p;
                 ratio > 0.8
             }
         }
@@ -87,13 +101,108 @@ impl TextLine {
     pub fn get_text(&self) -> String {
         let mut s = String::new();
         for (i, span) in self.spans.iter().enumerate() {
+            let text = span.text.trim();
+            if text.is_empty() {
+                continue;
+            }
             if i > 0 {
-                s.push('\n');
+                // inline formulas stay apart from the running text
+                if span.label == LayoutLabel::Formula
+                    || self.spans[i - 1].label == LayoutLabel::Formula
+                {
+                    s.push(' ');
+                } else {
+                    join_text(&mut s, text);
+                    continue;
+                }
             }
-            s.push_str(&span.text);
+            s.push_str(text);
         }
         s
     }
+
+    // extent across the line direction, the glyph height of horizontal lines
+    fn thickness(&self) -> f32 {
+        match self.direction {
+            Direction::Horizontal => self.height,
+            Direction::Vertical => self.width,
+        }
+    }
+
+    // where the line starts along its direction
+    fn start(&self) -> f32 {
+        match self.direction {
+            Direction::Horizontal => self.region_box[0],
+            Direction::Vertical => self.region_box[1],
+        }
+    }
+
+    // space between this line and the next one
+    fn gap_to(&self, next: &TextLine) -> f32 {
+        match self.direction {
+            Direction::Horizontal => next.region_box[1] - self.region_box[3],
+            Direction::Vertical => self.region_box[0] - next.region_box[2],
+        }
+    }
+}
+
+// append `next` to `text`: latin words are separated by a space, cjk text runs on without one and
+// a word hyphenated at the line end is joined again
+fn join_text(text: &mut String, next: &str) {
+    let Some(last) = text.chars().last() else {
+        text.push_str(next);
+        return;
+    };
+    let first = next.chars().next().unwrap_or(' ');
+    let before_hyphen = text.chars().rev().nth(1);
+    if last == '-' && before_hyphen.is_some_and(|c| c.is_alphabetic()) && first.is_lowercase() {
+        text.pop();
+    } else if !(is_cjk(last) || is_cjk(first)) {
+        text.push(' ');
+    }
+    text.push_str(next);
+}
+
+// scripts written without spaces between words
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3000}'..='\u{30ff}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{f900}'..='\u{faff}'
+            | '\u{ff00}'..='\u{ffef}'
+    )
+}
+
+// join the lines of a block into paragraphs, a new paragraph starts after a gap wider than a line
+// or at a line indented while the line before is not
+fn assemble_paragraphs(lines: &[TextLine]) -> String {
+    let block_start = lines.iter().map(|l| l.start()).fold(f32::MAX, f32::min);
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut prev: Option<&TextLine> = None;
+    for line in lines.iter() {
+        let text = line.get_text();
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(prev) = prev {
+            let size = prev.thickness().min(line.thickness()).max(1.0);
+            let indented =
+                line.start() - block_start > size && prev.start() - block_start < size * 0.5;
+            if prev.gap_to(line) > size * 0.8 || indented {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+        }
+        join_text(&mut current, text);
+        prev = Some(line);
+    }
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+    paragraphs.join("\n\n")
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -102,6 +211,15 @@ pub enum Direction {
     Vertical,
 }
 
+// part of a paragraph continued from another column or page, `start` is the byte offset of its
+// text in the content of the block it was merged into
+#[derive(Debug, Clone)]
+pub struct BlockFragment {
+    pub page: usize,
+    pub bbox: [f32; 4],
+    pub start: usize,
+}
+
 #[derive(Debug)]
 pub struct LayoutBlock {
     pub label: LayoutLabel,
@@ -113,6 +231,17 @@ pub struct LayoutBlock {
     pub text_line_height: f32,
     pub child_blocks: Vec<LayoutBlock>,
     pub num_of_lines: usize,
+    pub formula_number: Option<String>,
+    pub table: Option<TableResult>,
+    pub caption: Option<String>,
+    // position of the block in reading order
+    pub order_index: usize,
+    // text column of the page holding the block, none for blocks spanning columns and for
+    // page headers and footers
+    pub column: Option<usize>,
+    // markdown heading level of titles, 1 to 6
+    pub heading_level: Option<u8>,
+    pub fragments: Vec<BlockFragment>,
 }
 
 impl LayoutBlock {
@@ -124,6 +253,13 @@ impl LayoutBlock {
         let text_line_height = 0.0;
         let child_blocks = Vec::new();
         let num_of_lines = 0_usize;
+        let formula_number = None;
+        let table = None;
+        let caption = None;
+        let order_index = 0;
+        let column = None;
+        let heading_level = None;
+        let fragments = Vec::new();
         Self {
             label,
             bbox,
@@ -134,6 +270,13 @@ impl LayoutBlock {
             text_line_height,
             child_blocks,
             num_of_lines,
+            formula_number,
+            table,
+            caption,
+            order_index,
+            column,
+            heading_level,
+            fragments,
         }
     }
 
@@ -146,11 +289,11 @@ impl LayoutBlock {
         [x, y]
     }
 
-    fn calculate_text_line_direction(&self, ocr_res: &[OcrResultItem]) -> Direction {
-        let num_of_item = ocr_res.len();
+    fn calculate_text_line_direction(&self, spans: &[TextSpan]) -> Direction {
+        let num_of_item = spans.len();
         let mut h = 0;
-        for item in ocr_res {
-            let [x1, y1, x2, y2] = item.bbox;
+        for span in spans {
+            let [x1, y1, x2, y2] = span.bbox;
             let width = x2 - x1;
             let height = y2 - y1;
             if width * 1.5 > height {
@@ -163,25 +306,24 @@ impl LayoutBlock {
         Direction::Vertical
     }
 
-    fn group_content_to_lines(&mut self, ocr_res: &[OcrResultItem]) -> Vec<TextLine> {
-        self.direction = self.calculate_text_line_direction(ocr_res);
-        let mut sorted_ocr_items = ocr_res.to_vec();
+    fn group_content_to_lines(&mut self, spans: &[TextSpan]) -> Vec<TextLine> {
+        self.direction = self.calculate_text_line_direction(spans);
+        let mut sorted_spans = spans.to_vec();
         match self.direction {
             Direction::Horizontal => {
-                sorted_ocr_items.sort_by(|a, b| a.bbox[1].total_cmp(&b.bbox[1]));
+                sorted_spans.sort_by(|a, b| a.bbox[1].total_cmp(&b.bbox[1]));
             }
             Direction::Vertical => {
-                sorted_ocr_items.sort_by(|a, b| b.bbox[0].total_cmp(&a.bbox[0]));
+                sorted_spans.sort_by(|a, b| b.bbox[0].total_cmp(&a.bbox[0]));
             }
         }
         let mut res = Vec::new();
-        if sorted_ocr_items.is_empty() {
+        if sorted_spans.is_empty() {
             return res;
         }
         let mut current_line = TextLine::default();
         current_line.direction = self.direction.clone();
-        for item in sorted_ocr_items.iter() {
-            let span = TextSpan::new_from_ocr(item);
+        for span in sorted_spans.into_iter() {
             if current_line.spans.is_empty() {
                 current_line.add_span(span);
             } else {
@@ -190,6 +332,7 @@ impl LayoutBlock {
                 } else {
                     res.push(current_line.clone());
                     current_line = TextLine::default();
+                    current_line.direction = self.direction.clone();
                     current_line.add_span(span);
                 }
             }
@@ -197,6 +340,17 @@ impl LayoutBlock {
         if !current_line.spans.is_empty() {
             res.push(current_line);
         }
+        // order the spans along the line so inline formulas sit between their text
+        for line in res.iter_mut() {
+            match self.direction {
+                Direction::Horizontal => {
+                    line.spans.sort_by(|a, b| a.bbox[0].total_cmp(&b.bbox[0]));
+                }
+                Direction::Vertical => {
+                    line.spans.sort_by(|a, b| a.bbox[1].total_cmp(&b.bbox[1]));
+                }
+            }
+        }
         let all_width: f32 = res.iter().map(|v| v.region_box[2] - v.region_box[0]).sum();
         if !res.is_empty() {
             self.text_line_width = all_width / res.len() as f32;
@@ -209,16 +363,99 @@ impl LayoutBlock {
     }
 
     pub fn update_text_content(&mut self, ocr_res: &[OcrResultItem]) {
-        let lines = self.group_content_to_lines(ocr_res);
-        let mut content = String::new();
-        for line in lines {
-            content.push_str(line.get_text().as_str());
+        let spans: Vec<TextSpan> = ocr_res.iter().map(TextSpan::new_from_ocr).collect();
+        self.update_text_spans(spans.as_slice());
+    }
+
+    pub fn update_text_spans(&mut self, spans: &[TextSpan]) {
+        let lines = self.group_content_to_lines(spans);
+        self.num_of_lines = lines.len();
+        self.content = assemble_paragraphs(lines.as_slice());
+    }
+    // a paragraph ending without punctuation at the bottom of a column goes on in the first text
+    // block of the next column or page
+    pub fn is_continued_by(&self, next: &LayoutBlock) -> bool {
+        if self.label != LayoutLabel::Text || next.label != LayoutLabel::Text {
+            return false;
         }
-        self.content = content;
+        let ending = self
+            .content
+            .trim_end()
+            .trim_end_matches(['"', '\'', '”', '’', '」', '』', ')', '）']);
+        let (Some(last), Some(first)) = (
+            ending.chars().last(),
+            next.content.trim_start().chars().next(),
+        ) else {
+            return false;
+        };
+        if ".!?:;。！？：；…".contains(last) {
+            return false;
+        }
+        let starts_mid_sentence = first.is_lowercase() || (is_cjk(last) && is_cjk(first));
+        // both parts set in the same size
+        let (a, b) = (self.text_line_height, next.text_line_height);
+        let same_style = a <= 0.0 || b <= 0.0 || (a - b).abs() <= a.max(b) * 0.25;
+        starts_mid_sentence && same_style
     }
+
+    // append the continuation found on page `page`, its origin is kept as a fragment
+    pub fn append_continuation(&mut self, next: LayoutBlock, page: usize) {
+        let next_content = next.content.trim();
+        join_text(&mut self.content, next_content);
+        let start = self.content.len() - next_content.len();
+        self.fragments.push(BlockFragment {
+            page,
+            bbox: next.bbox,
+            start,
+        });
+        self.num_of_lines += next.num_of_lines;
+    }
+
     pub fn set_table_content(&mut self, content: String) {
         self.content = content;
     }
+    pub fn set_table(&mut self, table: TableResult) {
+        self.content = table.to_html();
+        self.table = Some(table);
+    }
+    pub fn set_caption(&mut self, caption: &str) {
+        let caption = caption.split_whitespace().join(" ");
+        self.caption = (!caption.is_empty()).then_some(caption);
+    }
+    pub fn set_seal_text(&mut self, seal: &SealText) {
+        self.content = seal.to_text();
+    }
+    // data table read from a chart, the chart itself stays an image
+    pub fn set_chart_table(&mut self, table: TableResult) {
+        self.table = Some(table);
+    }
+    pub fn set_formula_content(&mut self, latex: String) {
+        self.content = latex;
+    }
+    pub fn set_formula_number(&mut self, number: &str) {
+        self.formula_number = normalize_formula_number(number);
+    }
+    // formula latex with its number as `\tag{}`
+    pub fn latex(&self) -> String {
+        match &self.formula_number {
+            Some(number) => format!("{} \\tag{{{}}}", self.content, number),
+            None => self.content.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownOptions {
+    pub table_format: TableFormat,
+    // directory the image links point into, relative to the markdown file, "images" when unset
+    pub image_dir: Option<String>,
+}
+
+// a figure crop referenced from the markdown, `name` is the link path
+#[derive(Debug)]
+pub struct ImageAsset<'a> {
+    pub name: String,
+    pub image: &'a RgbImage,
 }
 
 #[derive(Debug)]
@@ -228,6 +465,7 @@ pub struct LayoutRegion {
     text_line_width: f32,
     text_line_height: f32,
     direction: Direction,
+    columns: Vec<[f32; 2]>,
 }
 
 impl LayoutRegion {
@@ -238,12 +476,32 @@ impl LayoutRegion {
             text_line_width: 20.0,
             text_line_height: 10.0,
             direction: Direction::Horizontal,
+            columns: Vec::new(),
         }
     }
     pub fn blocks(&self) -> &[LayoutBlock] {
         self.blocks.as_slice()
     }
 
+    pub fn blocks_mut(&mut self) -> &mut Vec<LayoutBlock> {
+        &mut self.blocks
+    }
+
+    // number the blocks in their current order, after blocks were merged away
+    pub fn renumber_blocks(&mut self) {
+        for (index, block) in self.blocks.iter_mut().enumerate() {
+            block.order_index = index;
+        }
+    }
+
+    // formula block numbered `number`, to resolve references like "Eq. (3)"
+    pub fn find_formula(&self, number: &str) -> Option<&LayoutBlock> {
+        let tag = normalize_formula_number(number)?;
+        self.blocks
+            .iter()
+            .find(|b| b.label == LayoutLabel::Formula && b.formula_number.as_ref() == Some(&tag))
+    }
+
     pub fn init_region_info(&mut self) {
         let mut horizone_num = 0;
         let mut text_line_widths = Vec::new();
@@ -276,32 +534,169 @@ impl LayoutRegion {
         };
     }
 
+    // x ranges of the text columns, left to right
+    pub fn columns(&self) -> &[[f32; 2]] {
+        self.columns.as_slice()
+    }
+
+    // find the text columns and put every body block into the column holding it
+    pub fn detect_columns(&mut self) {
+        // vertical text is read in columns of lines, not in page columns
+        if self.direction == Direction::Vertical {
+            self.columns.clear();
+        } else {
+            self.columns = detect_columns(self.blocks.as_slice());
+        }
+        for block in self.blocks.iter_mut() {
+            block.column = match block.label {
+                LayoutLabel::Header
+                | LayoutLabel::Footer
+                | LayoutLabel::Number
+                | LayoutLabel::AsideText => None,
+                _ => column_of(&block.bbox, self.columns.as_slice()),
+            };
+        }
+    }
+
     pub fn sort_blocks(&mut self) {
-        self.blocks.sort_by(|a, b| {
-            cmp_block(
-                a,
-                b,
-                &self.direction,
-                self.text_line_width,
-                self.text_line_height,
-            )
-        });
+        self.init_region_info();
+        self.detect_columns();
+        // vertical text runs in columns from right to left
+        let right_to_left = self.direction == Direction::Vertical;
+        let order = reading_order(
+            self.blocks.as_slice(),
+            self.columns.len(),
+            right_to_left,
+            self.text_line_height * 0.3,
+        );
+        for (index, block_id) in order.into_iter().enumerate() {
+            self.blocks[block_id].order_index = index;
+        }
+        self.blocks.sort_by_key(|b| b.order_index);
     }
 
     pub fn to_markdown(&self) -> Result<String> {
+        self.to_markdown_with_options(&MarkdownOptions::default())
+    }
+
+    pub fn to_markdown_with_options(&self, options: &MarkdownOptions) -> Result<String> {
+        self.render_markdown(options, None)
+    }
+
+    // markdown linking the image, chart and seal crops of page `page` (1-based), the crops are
+    // returned named by their link path
+    pub fn to_markdown_with_assets(
+        &self,
+        options: &MarkdownOptions,
+        page: usize,
+    ) -> Result<(String, Vec<ImageAsset<'_>>)> {
+        let mut assets = Vec::new();
+        let markdown = self.render_markdown(options, Some((page, &mut assets)))?;
+        Ok((markdown, assets))
+    }
+
+    // write the crops under `dir` and return the markdown linking them
+    pub fn write_markdown_assets<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        options: &MarkdownOptions,
+        page: usize,
+    ) -> Result<String> {
+        let (markdown, assets) = self.to_markdown_with_assets(options, page)?;
+        for asset in assets.iter() {
+            let path = dir.as_ref().join(asset.name.as_str());
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            asset.image.save(path)?;
+        }
+        Ok(markdown)
+    }
+
+    fn render_markdown<'a>(
+        &'a self,
+        options: &MarkdownOptions,
+        mut assets: Option<(usize, &mut Vec<ImageAsset<'a>>)>,
+    ) -> Result<String> {
+        let image_dir = options.image_dir.as_deref().unwrap_or("images");
+        let mut counters: HashMap<&str, usize> = HashMap::new();
         let mut markdown = String::new();
         for block in self.blocks.iter() {
             match block.label {
                 LayoutLabel::Table => {
-                    markdown.push_str(block.content.as_str());
-                    markdown.push_str("\n");
+                    let mut content = match (&block.table, options.table_format) {
+                        (Some(table), TableFormat::Csv) => format!("```csv\n{}```", table.to_csv()),
+                        (Some(table), TableFormat::Json) => {
+                            format!("```json\n{}\n```", table.to_json())
+                        }
+                        (Some(table), format) => table.render(format),
+                        (None, _) => block.content.to_owned(),
+                    };
+                    if let Some(caption) = &block.caption {
+                        // an html table carries its caption inside
+                        match &block.table {
+                            Some(table) if content.starts_with("<table") => {
+                                content = table.to_html_with_caption(caption);
+                            }
+                            _ => markdown.push_str(format!("*{}*\n\n", caption).as_str()),
+                        }
+                    }
+                    markdown.push_str(format!("{}\n\n", content.trim_end()).as_str());
+                }
+                LayoutLabel::Image | LayoutLabel::Chart | LayoutLabel::Seal => {
+                    match (&mut assets, &block.image) {
+                        (Some((page, assets)), Some(image)) => {
+                            let kind = match block.label {
+                                LayoutLabel::Chart => "chart",
+                                LayoutLabel::Seal => "seal",
+                                _ => "fig",
+                            };
+                            let counter = counters.entry(kind).or_default();
+                            *counter += 1;
+                            let name =
+                                format!("{}/page{}_{}{}.png", image_dir, page, kind, counter);
+                            let alt = block
+                                .caption
+                                .as_deref()
+                                .unwrap_or_default()
+                                .replace('[', "\\[")
+                                .replace(']', "\\]");
+                            markdown.push_str(format!("![{}]({})\n\n", alt, name).as_str());
+                            assets.push(ImageAsset { name, image });
+                        }
+                        _ => {
+                            if let Some(caption) = &block.caption {
+                                markdown.push_str(format!("*{}*\n\n", caption).as_str());
+                            }
+                        }
+                    }
+                    // text read from a seal
+                    if !block.content.is_empty() {
+                        markdown.push_str(format!("{}\n\n", block.content).as_str());
+                    }
+                    // the data read from a chart goes under it
+                    if let Some(table) = &block.table {
+                        markdown
+                            .push_str(format!("{}\n\n", table.to_markdown().trim_end()).as_str());
+                    }
                 }
-                LayoutLabel::Image => {}
-                LayoutLabel::ParaGraphTitle => {
-                    markdown.push_str(format!("## {} \n", block.content).as_str());
+                LayoutLabel::Formula => {
+                    if !block.content.is_empty() {
+                        markdown.push_str(format!("$$\n{}\n$$\n\n", block.latex()).as_str());
+                    } else if let Some(number) = &block.formula_number {
+                        // the formula was not recognized, keep its number so references resolve
+                        markdown.push_str(format!("({})\n\n", number).as_str());
+                    }
                 }
-                LayoutLabel::Abstract | LayoutLabel::FigureTitle | LayoutLabel::DocTitle => {
-                    markdown.push_str(format!("# {} \n", block.content).as_str());
+                LayoutLabel::ParaGraphTitle | LayoutLabel::DocTitle => {
+                    let default_level = if block.label == LayoutLabel::DocTitle {
+                        1
+                    } else {
+                        2
+                    };
+                    let level = block.heading_level.unwrap_or(default_level) as usize;
+                    markdown
+                        .push_str(format!("{} {}\n\n", "#".repeat(level), block.content).as_str());
                 }
                 LayoutLabel::Footnote
                 | LayoutLabel::Header
@@ -312,12 +707,12 @@ impl LayoutRegion {
                 | LayoutLabel::ReferenceContent
                 | LayoutLabel::Reference
                 | LayoutLabel::Text
+                | LayoutLabel::FormulaNumber
+                | LayoutLabel::FigureTitle
+                | LayoutLabel::Abstract
                 | LayoutLabel::AsideText => {
-                    markdown.push_str(block.content.as_str());
-                    markdown.push_str("\n");
-                }
-                _ => {
-                    println!("not hanled label:{:?}", block.label);
+                    // a blank line keeps adjacent blocks apart as markdown paragraphs
+                    markdown.push_str(format!("{}\n\n", block.content).as_str());
                 }
             }
         }
@@ -325,57 +720,47 @@ impl LayoutRegion {
     }
 }
 
-pub fn cmp_block(
-    a: &LayoutBlock,
-    b: &LayoutBlock,
-    direction: &Direction,
-    text_line_width: f32,
-    text_line_height: f32,
-) -> Ordering {
-    if direction == &Direction::Horizontal {
-        let y1 = a.bbox[1].round() as u32 / (text_line_height.round() as u32);
-        let y2 = b.bbox[1].round() as u32 / (text_line_height.round() as u32);
-        match y1.cmp(&y2) {
-            Ordering::Equal => {
-                let x1 = a.bbox[0].round() as u32 / (text_line_width.round() as u32);
-                let x2 = b.bbox[0].round() as u32 / (text_line_width.round() as u32);
-                match x1.cmp(&x2) {
-                    Ordering::Less => Ordering::Less,
-                    Ordering::Greater => Ordering::Greater,
-                    Ordering::Equal => {
-                        let center1 = a.center();
-                        let dist1 = center1[0] * center1[0] + center1[1] * center1[1];
-                        let center2 = b.center();
-                        let dist2 = center2[0] * center2[0] + center2[1] * center2[1];
-                        dist1.total_cmp(&dist2)
-                    }
-                }
-            }
-            Ordering::Less => Ordering::Less,
-            Ordering::Greater => Ordering::Greater,
-        }
+// "(3)" -> "3"
+fn normalize_formula_number(number: &str) -> Option<String> {
+    let number = number
+        .trim()
+        .trim_start_matches(['(', '（'])
+        .trim_end_matches([')', '）'])
+        .trim();
+    if number.is_empty() {
+        None
     } else {
-        let x1 = a.bbox[0].round() as u32 / (text_line_width.round() as u32);
-        let x2 = b.bbox[0].round() as u32 / (text_line_width.round() as u32);
-
-        match x1.cmp(&x2) {
-            Ordering::Equal => {
-                let y1 = a.bbox[1].round() as u32 / (text_line_width.round() as u32);
-                let y2 = b.bbox[1].round() as u32 / (text_line_width.round() as u32);
-                match y1.cmp(&y2) {
-                    Ordering::Less => Ordering::Greater,
-                    Ordering::Greater => Ordering::Less,
-                    Ordering::Equal => {
-                        let center1 = a.center();
-                        let dist1 = center1[0] * center1[0] + center1[1] * center1[1];
-                        let center2 = b.center();
-                        let dist2 = center2[0] * center2[0] + center2[1] * center2[1];
-                        dist1.total_cmp(&dist2).reverse()
-                    }
-                }
-            }
-            Ordering::Less => Ordering::Greater,
-            Ordering::Greater => Ordering::Less,
+        Some(number.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joined(parts: &[&str]) -> String {
+        let mut text = String::new();
+        for part in parts.iter() {
+            join_text(&mut text, part);
         }
+        text
+    }
+
+    #[test]
+    fn join_text_spaces_latin_words() {
+        assert_eq!(joined(&["the quick", "brown fox"]), "the quick brown fox");
+    }
+
+    #[test]
+    fn join_text_rejoins_hyphenated_words() {
+        assert_eq!(joined(&["recog-", "nition"]), "recognition");
+        // a dash standing alone is not a hyphenation
+        assert_eq!(joined(&["a -", "b"]), "a - b");
+    }
+
+    #[test]
+    fn join_text_runs_cjk_on() {
+        assert_eq!(joined(&["文档解析", "模型"]), "文档解析模型");
+        assert_eq!(joined(&["使用", "ONNX", "推理"]), "使用ONNX推理");
     }
 }
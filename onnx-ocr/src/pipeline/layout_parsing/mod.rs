@@ -1,3 +1,5 @@
 pub mod doc_analysise;
+pub mod heading;
 pub mod layout_object;
+mod reading_order;
 mod util;
@@ -0,0 +1,273 @@
+use crate::{
+    doc_layout::predictor::LayoutLabel, pipeline::layout_parsing::layout_object::LayoutBlock,
+};
+
+const BODY: usize = 2;
+
+// page headers, document titles, the body, page footers and margin notes are read in this order
+fn order_group(label: &LayoutLabel) -> usize {
+    match label {
+        LayoutLabel::Header => 0,
+        LayoutLabel::DocTitle => 1,
+        LayoutLabel::Footer | LayoutLabel::Number => 3,
+        LayoutLabel::AsideText => 4,
+        _ => BODY,
+    }
+}
+
+// x ranges of the text columns of the page, found from the gaps between the narrow body blocks.
+// a single column page has one range
+pub fn detect_columns(blocks: &[LayoutBlock]) -> Vec<[f32; 2]> {
+    let body: Vec<&LayoutBlock> = blocks
+        .iter()
+        .filter(|b| order_group(&b.label) == BODY)
+        .collect();
+    if body.is_empty() {
+        return Vec::new();
+    }
+    let left = body.iter().map(|b| b.bbox[0]).fold(f32::MAX, f32::min);
+    let right = body.iter().map(|b| b.bbox[2]).fold(f32::MIN, f32::max);
+    let mut narrow: Vec<[f32; 2]> = body
+        .iter()
+        .filter(|b| !is_title(&b.label) && b.bbox[2] - b.bbox[0] <= (right - left) * 0.5)
+        .map(|b| [b.bbox[0], b.bbox[2]])
+        .collect();
+    narrow.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    let mut columns: Vec<[f32; 2]> = Vec::new();
+    for [x1, x2] in narrow {
+        match columns.last_mut() {
+            Some(column) if x1 < column[1] => column[1] = column[1].max(x2),
+            _ => columns.push([x1, x2]),
+        }
+    }
+    if columns.len() < 2 {
+        return vec![[left, right]];
+    }
+    // the outer columns reach the page body edges
+    columns[0][0] = left;
+    if let Some(last) = columns.last_mut() {
+        last[1] = right;
+    }
+    columns
+}
+
+// the column holding `bbox`, none when it crosses the gap between two columns
+pub fn column_of(bbox: &[f32; 4], columns: &[[f32; 2]]) -> Option<usize> {
+    let crosses_gap = columns
+        .windows(2)
+        .map(|pair| (pair[0][1] + pair[1][0]) / 2.0)
+        .any(|gap| bbox[0] < gap && bbox[2] > gap);
+    if crosses_gap {
+        return None;
+    }
+    columns
+        .iter()
+        .enumerate()
+        .map(|(id, column)| (id, bbox[2].min(column[1]) - bbox[0].max(column[0])))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _)| id)
+}
+
+// reading order of the blocks. with several columns the body is cut into bands at the blocks
+// spanning columns and every band is read column by column, each column in xy-cut order
+pub fn reading_order(
+    blocks: &[LayoutBlock],
+    column_count: usize,
+    right_to_left: bool,
+    y_tolerance: f32,
+) -> Vec<usize> {
+    let cutter = XyCut {
+        boxes: blocks.iter().map(|b| b.bbox).collect(),
+        titles: blocks.iter().map(|b| is_title(&b.label)).collect(),
+        right_to_left,
+        y_tolerance,
+    };
+    let mut groups: [Vec<usize>; 5] = Default::default();
+    for (id, block) in blocks.iter().enumerate() {
+        groups[order_group(&block.label)].push(id);
+    }
+    let mut order = Vec::with_capacity(blocks.len());
+    for (group, ids) in groups.into_iter().enumerate() {
+        if group != BODY {
+            order.extend(cutter.top_down(ids));
+        } else if column_count < 2 {
+            cutter.cut(ids, &mut order);
+        } else {
+            let (spanning, rest): (Vec<usize>, Vec<usize>) =
+                ids.into_iter().partition(|id| blocks[*id].column.is_none());
+            let spanning = cutter.top_down(spanning);
+            for (band, ids) in cutter
+                .bands(spanning.as_slice(), rest)
+                .into_iter()
+                .enumerate()
+            {
+                let mut columns: Vec<Vec<usize>> = vec![Vec::new(); column_count];
+                for id in ids {
+                    columns[blocks[id].column.unwrap_or_default().min(column_count - 1)].push(id);
+                }
+                if right_to_left {
+                    columns.reverse();
+                }
+                for column in columns {
+                    cutter.cut(column, &mut order);
+                }
+                if let Some(id) = spanning.get(band) {
+                    order.push(*id);
+                }
+            }
+        }
+    }
+    order
+}
+
+// titles may be centered over the gap between columns
+fn is_title(label: &LayoutLabel) -> bool {
+    matches!(
+        label,
+        LayoutLabel::DocTitle | LayoutLabel::ParaGraphTitle | LayoutLabel::FigureTitle
+    )
+}
+
+struct XyCut {
+    boxes: Vec<[f32; 4]>,
+    titles: Vec<bool>,
+    right_to_left: bool,
+    // blocks overlapping by less than this in y count as separate rows
+    y_tolerance: f32,
+}
+
+impl XyCut {
+    // recursive xy-cut: the blocks are split at the gaps of their projection on the x axis first
+    // so side by side columns are read one after the other, then at the gaps on the y axis
+    fn cut(&self, ids: Vec<usize>, order: &mut Vec<usize>) {
+        if ids.len() <= 1 {
+            order.extend(ids);
+            return;
+        }
+        let mut columns = self.split_projection(ids.as_slice(), 0, 0.0);
+        if columns.len() > 1 {
+            if self.right_to_left {
+                columns.reverse();
+            }
+            for column in columns {
+                self.cut(column, order);
+            }
+            return;
+        }
+        if self.cut_at_spanning_blocks(ids.as_slice(), order) {
+            return;
+        }
+        let rows = self.split_projection(ids.as_slice(), 1, self.y_tolerance);
+        if rows.len() > 1 {
+            for row in rows {
+                self.cut(row, order);
+            }
+            return;
+        }
+        // overlapping blocks can not be cut
+        order.extend(self.top_down(ids));
+    }
+
+    // full width blocks and titles over the column gap leave no x gap, and cutting at every y gap
+    // instead would interleave columns whose paragraphs happen to line up. the columns are found
+    // from the narrow blocks, the blocks crossing a column gap then cut the page into bands which
+    // are read column by column
+    fn cut_at_spanning_blocks(&self, ids: &[usize], order: &mut Vec<usize>) -> bool {
+        let left = ids
+            .iter()
+            .map(|id| self.boxes[*id][0])
+            .fold(f32::MAX, f32::min);
+        let right = ids
+            .iter()
+            .map(|id| self.boxes[*id][2])
+            .fold(f32::MIN, f32::max);
+        let narrow: Vec<usize> = ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                let [x1, _, x2, _] = self.boxes[*id];
+                !self.titles[*id] && x2 - x1 <= (right - left) * 0.5
+            })
+            .collect();
+        let columns = self.split_projection(narrow.as_slice(), 0, 0.0);
+        if columns.len() < 2 {
+            return false;
+        }
+        let gaps: Vec<f32> = columns
+            .windows(2)
+            .map(|pair| {
+                let end = pair[0]
+                    .iter()
+                    .map(|id| self.boxes[*id][2])
+                    .fold(f32::MIN, f32::max);
+                let start = pair[1]
+                    .iter()
+                    .map(|id| self.boxes[*id][0])
+                    .fold(f32::MAX, f32::min);
+                (end + start) / 2.0
+            })
+            .collect();
+        let (mut spanning, rest): (Vec<usize>, Vec<usize>) = ids.iter().partition(|id| {
+            let [x1, _, x2, _] = self.boxes[**id];
+            gaps.iter().any(|gap| x1 < *gap && x2 > *gap)
+        });
+        if spanning.is_empty() || rest.is_empty() {
+            return false;
+        }
+        spanning.sort_by(|a, b| self.boxes[*a][1].total_cmp(&self.boxes[*b][1]));
+        for (band, ids) in self
+            .bands(spanning.as_slice(), rest)
+            .into_iter()
+            .enumerate()
+        {
+            self.cut(ids, order);
+            if let Some(id) = spanning.get(band) {
+                order.push(*id);
+            }
+        }
+        true
+    }
+
+    // the blocks above the first spanning block, between the first and the second and so on,
+    // `spanning` is sorted top down
+    fn bands(&self, spanning: &[usize], rest: Vec<usize>) -> Vec<Vec<usize>> {
+        let center = |id: usize| (self.boxes[id][1] + self.boxes[id][3]) / 2.0;
+        let mut bands: Vec<Vec<usize>> = vec![Vec::new(); spanning.len() + 1];
+        for id in rest {
+            let band = spanning.iter().filter(|s| center(**s) < center(id)).count();
+            bands[band].push(id);
+        }
+        bands
+    }
+
+    // groups of blocks separated by gaps of the projection on `axis` (0 for x, 1 for y), blocks
+    // overlapping by less than `tolerance` count as separated
+    fn split_projection(&self, ids: &[usize], axis: usize, tolerance: f32) -> Vec<Vec<usize>> {
+        let mut sorted = ids.to_vec();
+        sorted.sort_by(|a, b| self.boxes[*a][axis].total_cmp(&self.boxes[*b][axis]));
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut end = f32::MIN;
+        for id in sorted {
+            let [start, stop] = [self.boxes[id][axis], self.boxes[id][axis + 2]];
+            match groups.last_mut() {
+                Some(group) if start < end - tolerance => group.push(id),
+                _ => groups.push(vec![id]),
+            }
+            end = end.max(stop);
+        }
+        groups
+    }
+
+    fn top_down(&self, mut ids: Vec<usize>) -> Vec<usize> {
+        ids.sort_by(|a, b| {
+            let (a, b) = (&self.boxes[*a], &self.boxes[*b]);
+            let x = if self.right_to_left {
+                b[2].total_cmp(&a[2])
+            } else {
+                a[0].total_cmp(&b[0])
+            };
+            a[1].total_cmp(&b[1]).then(x)
+        });
+        ids
+    }
+}
@@ -1,3 +1,6 @@
 pub mod layout_parsing;
 pub mod ocr;
+pub mod ruled_table;
+pub mod seal;
 pub mod table;
+pub mod xlsx;
@@ -1,32 +1,93 @@
-use crate::{common::quad::Quad, model_context::ModelContext};
+use std::collections::HashMap;
+
+use crate::{
+    common::quad::Quad,
+    model_context::ModelContext,
+    text_recognition::{predictor::Language, script::identify_language},
+};
 use anyhow::Result;
 use image::{Rgb, RgbImage, imageops::rotate90};
 use imageproc::geometric_transformations::{Interpolation, warp_into};
 
+// lines whose mean character score is below this are retried with the other registered
+// recognizers
+const LOW_CONFIDENCE: f32 = 0.8;
+
 #[derive(Debug, Clone)]
 pub struct OcrResultItem {
     pub polys: Quad,
     pub content: String,
     pub bbox: [f32; 4],
+    pub language: Language,
+    pub score: f32,
 }
 
 impl OcrResultItem {
-    pub fn new(polys: Quad, content: String, bbox: [f32; 4]) -> Self {
+    pub fn new(
+        polys: Quad,
+        content: String,
+        bbox: [f32; 4],
+        language: Language,
+        score: f32,
+    ) -> Self {
         OcrResultItem {
             polys,
             content,
             bbox,
+            language,
+            score,
         }
     }
 }
 
+// per-line and per-page routing are opt-in, `ocr` reads every line with the default recognizer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageRouting {
+    // recognize every line with the recognizer of one language
+    Fixed(Language),
+    // route every line to the recognizer matching its script
+    PerLine,
+    // route the whole page to the language of the majority of its lines
+    PerPage,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecognizedText {
+    pub content: String,
+    pub score: f32,
+    // mean score of the decoded characters, what routing compares recognizers by
+    pub mean_score: f32,
+    pub language: Language,
+}
+
 pub fn ocr(context: &ModelContext, img: &RgbImage) -> Result<Vec<OcrResultItem>> {
+    ocr_with_routing(
+        context,
+        img,
+        LanguageRouting::Fixed(context.default_language()),
+    )
+}
+
+pub fn ocr_with_routing(
+    context: &ModelContext,
+    img: &RgbImage,
+    routing: LanguageRouting,
+) -> Result<Vec<OcrResultItem>> {
     let detect_predictor = &context.text_det_predictor;
-    let rec_predictor = &context.text_rec_predictor;
     let result = detect_predictor.predict_image(img)?;
+    recognize_polys(context, img, result.polys.as_slice(), routing)
+}
+
+// recognize the text inside already detected polygons
+pub fn recognize_polys(
+    context: &ModelContext,
+    img: &RgbImage,
+    quads: &[Quad],
+    routing: LanguageRouting,
+) -> Result<Vec<OcrResultItem>> {
     let mut images = Vec::new();
-    let mut n = 0;
-    for poly in result.polys.iter() {
+    let mut polys = Vec::new();
+    for poly in quads.iter() {
         if let Some(projection) = poly.projection() {
             let mut dest = RgbImage::new(poly.width.ceil() as u32, poly.height.ceil() as u32);
             warp_into(
@@ -45,23 +106,128 @@ pub fn ocr(context: &ModelContext, img: &RgbImage) -> Result<Vec<OcrResultItem>>
             } else {
                 images.push(dest);
             }
-            n += 1;
+            polys.push(poly.to_owned());
         } else {
             println!("projection is none");
         }
     }
-    let texts = rec_predictor.predict(images)?;
+    let texts = recognize(context, images, routing)?;
     let mut ocr_items = Vec::new();
-    for i in 0..texts.len() {
-        let poly = &result.polys[i];
-        let content = &texts[i].0;
-
-        let item = OcrResultItem {
-            polys: poly.to_owned(),
-            content: content.to_owned(),
-            bbox: poly.bbox(),
-        };
+    for (poly, text) in polys.into_iter().zip(texts) {
+        let bbox = poly.bbox();
+        let item = OcrResultItem::new(poly, text.content, bbox, text.language, text.score);
         ocr_items.push(item);
     }
     Ok(ocr_items)
 }
+
+pub fn recognize(
+    context: &ModelContext,
+    images: Vec<RgbImage>,
+    routing: LanguageRouting,
+) -> Result<Vec<RecognizedText>> {
+    match routing {
+        LanguageRouting::Fixed(language) => recognize_with(context, language, images),
+        LanguageRouting::PerLine => {
+            let mut texts = recognize_with(context, context.default_language(), images.clone())?;
+            route_lines(context, &images, &mut texts)?;
+            Ok(texts)
+        }
+        LanguageRouting::PerPage => {
+            let primary = recognize_with(context, context.default_language(), images.clone())?;
+            let mut texts = primary.clone();
+            route_lines(context, &images, &mut texts)?;
+            let page_language = majority_language(&texts).unwrap_or(context.default_language());
+            if page_language == context.default_language() {
+                return Ok(primary);
+            }
+            recognize_with(context, page_language, images)
+        }
+    }
+}
+
+fn recognize_with(
+    context: &ModelContext,
+    language: Language,
+    images: Vec<RgbImage>,
+) -> Result<Vec<RecognizedText>> {
+    let (language, predictor) = context.text_rec_predictor_for(language);
+    let texts = predictor.predict_with_mean_score(images)?;
+    let result = texts
+        .into_iter()
+        .map(|(content, score, mean_score)| RecognizedText {
+            content,
+            score,
+            mean_score,
+            language,
+        })
+        .collect();
+    Ok(result)
+}
+
+// `texts` holds the default recognizer result. latin lines go to the english recognizer,
+// lines with a low score are retried with every other recognizer and replaced by the best
+// result whose script matches the recognizer language.
+fn route_lines(
+    context: &ModelContext,
+    images: &[RgbImage],
+    texts: &mut [RecognizedText],
+) -> Result<()> {
+    let default_language = context.default_language();
+    let mut routed: HashMap<Language, Vec<usize>> = HashMap::new();
+    let mut retry = Vec::new();
+    for (i, text) in texts.iter().enumerate() {
+        if text.mean_score < LOW_CONFIDENCE {
+            retry.push(i);
+        } else if let Some(language) = identify_language(&text.content)
+            && language != default_language
+        {
+            routed.entry(language).or_default().push(i);
+        }
+    }
+    for language in context.registered_languages() {
+        if language == default_language {
+            continue;
+        }
+        let mut ids = routed.remove(&language).unwrap_or_default();
+        let num_routed = ids.len();
+        ids.extend(retry.iter().copied());
+        if ids.is_empty() {
+            continue;
+        }
+        let sub_images = ids.iter().map(|i| images[*i].to_owned()).collect();
+        let candidates = recognize_with(context, language, sub_images)?;
+        for (n, (id, candidate)) in ids.into_iter().zip(candidates).enumerate() {
+            let current = &texts[id];
+            let routed_here = n < num_routed;
+            let better = candidate.mean_score > current.mean_score
+                && script_matches(&candidate.content, language);
+            if routed_here || better {
+                texts[id] = candidate;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn script_matches(content: &str, language: Language) -> bool {
+    match identify_language(content) {
+        Some(script) => script == language,
+        None => false,
+    }
+}
+
+// language read by most characters on the page
+fn majority_language(texts: &[RecognizedText]) -> Option<Language> {
+    let mut votes: HashMap<Language, usize> = HashMap::new();
+    for text in texts.iter() {
+        // badly read lines do not vote
+        if text.mean_score >= LOW_CONFIDENCE && identify_language(&text.content).is_some() {
+            *votes.entry(text.language).or_default() += text.content.chars().count();
+        }
+    }
+    votes
+        .into_iter()
+        .max_by_key(|(_, n)| *n)
+        .map(|(language, _)| language)
+}
@@ -0,0 +1,273 @@
+use image::{GrayImage, RgbImage, imageops::grayscale};
+use imageproc::contrast::otsu_level;
+
+// a cell of a fully ruled table, spans come from the missing ruling segments
+#[derive(Debug, Clone)]
+pub struct RuledCell {
+    pub coordinate: [f32; 4],
+    pub row: u32,
+    pub col: u32,
+    pub row_span: u32,
+    pub col_span: u32,
+}
+
+// a line mask is what survives an opening with a long horizontal (vertical) kernel:
+// only dark runs at least `min_len` long
+struct LineMask {
+    width: u32,
+    height: u32,
+    data: Vec<bool>,
+}
+
+impl LineMask {
+    fn get(&self, x: u32, y: u32) -> bool {
+        self.data[(y * self.width + x) as usize]
+    }
+
+    fn any_near(&self, x: u32, y: u32, dx: u32, dy: u32) -> bool {
+        let x1 = x.saturating_sub(dx);
+        let x2 = (x + dx).min(self.width - 1);
+        let y1 = y.saturating_sub(dy);
+        let y2 = (y + dy).min(self.height - 1);
+        (y1..=y2).any(|yy| (x1..=x2).any(|xx| self.get(xx, yy)))
+    }
+}
+
+// find the ruling lines of the table crop and build its cells from the line intersections,
+// returns nothing when the crop is not a ruled grid
+pub fn detect_ruled_cells(img: &RgbImage) -> Vec<RuledCell> {
+    let gray = grayscale(img);
+    let (w, h) = gray.dimensions();
+    if w < 8 || h < 8 {
+        return Vec::new();
+    }
+    let ink = binarize(&gray);
+    let min_h_len = (w / 20).max(20);
+    let min_v_len = (h / 20).max(20);
+    let mut h_mask = open_runs(&ink, w, h, min_h_len, true);
+    let mut v_mask = open_runs(&ink, w, h, min_v_len, false);
+    keep_largest_component(&mut h_mask, &mut v_mask);
+
+    let ys = line_positions(&h_mask, min_h_len, true);
+    let xs = line_positions(&v_mask, min_v_len, false);
+    if ys.len() < 2 || xs.len() < 2 {
+        return Vec::new();
+    }
+    build_cells(&h_mask, &v_mask, xs.as_slice(), ys.as_slice())
+}
+
+fn binarize(gray: &GrayImage) -> Vec<bool> {
+    let (min, max) = gray
+        .pixels()
+        .fold((255, 0), |(a, b), p| (p.0[0].min(a), p.0[0].max(b)));
+    // a flat crop has no ink, otsu would still split it in two
+    if max - min < 50 {
+        return vec![false; gray.len()];
+    }
+    let level = otsu_level(gray);
+    gray.pixels().map(|p| p.0[0] <= level).collect()
+}
+
+fn open_runs(ink: &[bool], w: u32, h: u32, min_len: u32, horizontal: bool) -> LineMask {
+    let mut data = vec![false; ink.len()];
+    let (outer, inner) = if horizontal { (h, w) } else { (w, h) };
+    let index = |o: u32, i: u32| {
+        if horizontal {
+            (o * w + i) as usize
+        } else {
+            (i * w + o) as usize
+        }
+    };
+    for o in 0..outer {
+        let mut start = 0;
+        let mut run = 0;
+        for i in 0..=inner {
+            if i < inner && ink[index(o, i)] {
+                if run == 0 {
+                    start = i;
+                }
+                run += 1;
+                continue;
+            }
+            if run >= min_len {
+                for j in start..start + run {
+                    data[index(o, j)] = true;
+                }
+            }
+            run = 0;
+        }
+    }
+    LineMask {
+        width: w,
+        height: h,
+        data,
+    }
+}
+
+// the table grid is the largest connected set of rules, a table nested with its own border
+// inside a cell is not connected to it and stays out of the outer grid
+fn keep_largest_component(h_mask: &mut LineMask, v_mask: &mut LineMask) {
+    let (w, h) = (h_mask.width as usize, h_mask.height as usize);
+    let ruled = |i: usize| h_mask.data[i] || v_mask.data[i];
+    let mut label = vec![0usize; w * h];
+    let mut best = (0, 0);
+    let mut next = 0;
+    for start in 0..w * h {
+        if !ruled(start) || label[start] != 0 {
+            continue;
+        }
+        next += 1;
+        label[start] = next;
+        let mut stack = vec![start];
+        let mut size = 0;
+        while let Some(i) = stack.pop() {
+            size += 1;
+            let (x, y) = (i % w, i / w);
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < w).then(|| i + 1),
+                (y > 0).then(|| i - w),
+                (y + 1 < h).then(|| i + w),
+            ];
+            for n in neighbours.into_iter().flatten() {
+                if ruled(n) && label[n] == 0 {
+                    label[n] = next;
+                    stack.push(n);
+                }
+            }
+        }
+        if size > best.1 {
+            best = (next, size);
+        }
+    }
+    for (i, l) in label.iter().enumerate() {
+        if *l != best.0 {
+            h_mask.data[i] = false;
+            v_mask.data[i] = false;
+        }
+    }
+}
+
+// rows (columns) of the mask holding a line, adjacent ones and double rules are merged
+fn line_positions(mask: &LineMask, min_len: u32, horizontal: bool) -> Vec<f32> {
+    let (outer, inner) = if horizontal {
+        (mask.height, mask.width)
+    } else {
+        (mask.width, mask.height)
+    };
+    let mut positions: Vec<f32> = Vec::new();
+    let mut group: Vec<u32> = Vec::new();
+    for o in 0..outer {
+        let count = (0..inner)
+            .filter(|i| {
+                if horizontal {
+                    mask.get(*i, o)
+                } else {
+                    mask.get(o, *i)
+                }
+            })
+            .count() as u32;
+        let is_line = count >= min_len;
+        if is_line && group.last().is_none_or(|last| o - last <= 1) {
+            group.push(o);
+            continue;
+        }
+        if !group.is_empty() {
+            positions.push(group.iter().sum::<u32>() as f32 / group.len() as f32);
+            group.clear();
+        }
+        if is_line {
+            group.push(o);
+        }
+    }
+    if !group.is_empty() {
+        positions.push(group.iter().sum::<u32>() as f32 / group.len() as f32);
+    }
+    let mut merged: Vec<f32> = Vec::new();
+    for p in positions {
+        match merged.last_mut() {
+            Some(last) if p - *last < 4.0 => *last = (*last + p) / 2.0,
+            _ => merged.push(p),
+        }
+    }
+    merged
+}
+
+// fraction of the segment between `from` and `to` covered by the line at `at`
+fn coverage(mask: &LineMask, at: f32, from: f32, to: f32, horizontal: bool) -> f32 {
+    let from = from as u32 + 2;
+    let to = (to as u32).saturating_sub(2);
+    if to <= from {
+        return 1.0;
+    }
+    let at = at.round() as u32;
+    let covered = (from..to)
+        .filter(|v| {
+            if horizontal {
+                mask.any_near(*v, at, 0, 2)
+            } else {
+                mask.any_near(at, *v, 2, 0)
+            }
+        })
+        .count();
+    covered as f32 / (to - from) as f32
+}
+
+fn build_cells(h_mask: &LineMask, v_mask: &LineMask, xs: &[f32], ys: &[f32]) -> Vec<RuledCell> {
+    let rows = ys.len() - 1;
+    let cols = xs.len() - 1;
+    let mut parent: Vec<usize> = (0..rows * cols).collect();
+    for r in 0..rows {
+        for c in 0..cols {
+            // no vertical rule between this interval and the right one: same cell
+            if c + 1 < cols && coverage(v_mask, xs[c + 1], ys[r], ys[r + 1], false) < 0.5 {
+                union(parent.as_mut_slice(), r * cols + c, r * cols + c + 1);
+            }
+            if r + 1 < rows && coverage(h_mask, ys[r + 1], xs[c], xs[c + 1], true) < 0.5 {
+                union(parent.as_mut_slice(), r * cols + c, (r + 1) * cols + c);
+            }
+        }
+    }
+
+    let mut groups: Vec<Option<(usize, usize, usize, usize)>> = vec![None; rows * cols];
+    for r in 0..rows {
+        for c in 0..cols {
+            let root = find(parent.as_mut_slice(), r * cols + c);
+            let g = groups[root].get_or_insert((r, c, r, c));
+            g.0 = g.0.min(r);
+            g.1 = g.1.min(c);
+            g.2 = g.2.max(r);
+            g.3 = g.3.max(c);
+        }
+    }
+    let mut cells: Vec<RuledCell> = groups
+        .into_iter()
+        .flatten()
+        .map(|(r1, c1, r2, c2)| RuledCell {
+            coordinate: [xs[c1], ys[r1], xs[c2 + 1], ys[r2 + 1]],
+            row: r1 as u32,
+            col: c1 as u32,
+            row_span: (r2 - r1 + 1) as u32,
+            col_span: (c2 - c1 + 1) as u32,
+        })
+        .collect();
+    cells.sort_by(|a, b| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
+    cells
+}
+
+fn find(parent: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    parent[i] = root;
+    root
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let ra = find(parent, a);
+    let rb = find(parent, b);
+    if ra != rb {
+        parent[rb] = ra;
+    }
+}
@@ -0,0 +1,276 @@
+use anyhow::Result;
+use image::{Rgb, RgbImage};
+use imageproc::geometric_transformations::{Interpolation, warp_into_with};
+use std::f32::consts::PI;
+
+use crate::{
+    model_context::ModelContext,
+    pipeline::{ocr, table::join_cell_lines},
+};
+
+// radius where the ring text band starts, relative to the seal radius
+const RING_INNER: f32 = 0.55;
+
+// rays cast for the border and how many must hit it for a fit
+const BORDER_RAYS: usize = 180;
+const MIN_BORDER_POINTS: usize = BORDER_RAYS / 4;
+
+// text of a round or oval stamp, the ring text runs along the border and the inner lines are
+// written horizontally inside it
+#[derive(Debug, Clone, Default)]
+pub struct SealText {
+    pub ring: String,
+    pub inner: String,
+    pub score: f32,
+}
+
+impl SealText {
+    pub fn to_text(&self) -> String {
+        [self.ring.as_str(), self.inner.as_str()]
+            .into_iter()
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ellipse {
+    cx: f32,
+    cy: f32,
+    rx: f32,
+    ry: f32,
+}
+
+impl Ellipse {
+    // 1.0 on the outline, below inside it
+    fn distance(&self, x: f32, y: f32) -> f32 {
+        let dx = (x - self.cx) / self.rx;
+        let dy = (y - self.cy) / self.ry;
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+pub fn recognize_seal(context: &ModelContext, img: &RgbImage) -> Result<SealText> {
+    let ink = seal_ink(img);
+    let ellipse = find_ellipse(&ink);
+
+    // the curved ring text becomes a straight line once the ring is unwrapped
+    let strip = polar_unwrap(&ink, ellipse);
+    let ring_items = ocr::ocr(context, &strip)?;
+    let ring_items: Vec<_> = ring_items.iter().collect();
+    let (ring, ring_score) = join_cell_lines(ring_items.as_slice());
+
+    let inner_img = clear_ring(&ink, ellipse);
+    let inner_items = ocr::ocr(context, &inner_img)?;
+    let inner_items: Vec<_> = inner_items.iter().collect();
+    let (inner, inner_score) = join_cell_lines(inner_items.as_slice());
+
+    let (ring_len, inner_len) = (ring.chars().count(), inner.chars().count());
+    let score = if ring_len + inner_len == 0 {
+        0.0
+    } else {
+        (ring_score * ring_len as f32 + inner_score * inner_len as f32)
+            / (ring_len + inner_len) as f32
+    };
+    Ok(SealText { ring, inner, score })
+}
+
+// red stamps are turned into dark ink on white so the text behind them disappears, stamps in
+// other colors are used as they are
+fn seal_ink(img: &RgbImage) -> RgbImage {
+    let redness = |p: &Rgb<u8>| {
+        let [r, g, b] = p.0.map(|v| v as i32);
+        r - (g + b) / 2
+    };
+    let red_count = img.pixels().filter(|p| redness(p) > 50).count();
+    if red_count * 100 < img.pixels().len() {
+        return img.to_owned();
+    }
+    let mut ink = img.to_owned();
+    for p in ink.pixels_mut() {
+        let v = (255 - redness(p).max(0) * 2).clamp(0, 255) as u8;
+        *p = Rgb([v, v, v]);
+    }
+    ink
+}
+
+// the seal outline is fitted to the outermost ink met by rays cast from the crop center. the
+// border ring is hit at a consistent distance on most rays, text or stray ink around the stamp
+// only on a few, those rays are dropped while the fit is refined. the box around all the ink is
+// used when no border is found
+fn find_ellipse(ink: &RgbImage) -> Ellipse {
+    let points = border_points(ink);
+    if points.len() >= MIN_BORDER_POINTS {
+        let mut radii: Vec<f32> = points.iter().map(|(_, _, r)| *r).collect();
+        radii.sort_by(|a, b| a.total_cmp(b));
+        let (w, h) = ink.dimensions();
+        let mut ellipse = Ellipse {
+            cx: w as f32 / 2.0,
+            cy: h as f32 / 2.0,
+            rx: radii[radii.len() / 2],
+            ry: radii[radii.len() / 2],
+        };
+        let mut fitted = None;
+        for tolerance in [0.35, 0.15, 0.08] {
+            let inliers: Vec<(f32, f32)> = points
+                .iter()
+                .filter(|(x, y, _)| (ellipse.distance(*x, *y) - 1.0).abs() <= tolerance)
+                .map(|(x, y, _)| (*x, *y))
+                .collect();
+            if inliers.len() < MIN_BORDER_POINTS {
+                break;
+            }
+            ellipse = fit_extent(inliers.as_slice());
+            fitted = Some(ellipse);
+        }
+        if let Some(ellipse) = fitted {
+            return ellipse;
+        }
+    }
+    ink_extent(ink)
+}
+
+fn is_ink(p: &Rgb<u8>) -> bool {
+    (p.0[0] as u32 + p.0[1] as u32 + p.0[2] as u32) / 3 < 128
+}
+
+// (x, y, distance from the center) of the outermost ink pixel on every ray that meets ink
+fn border_points(ink: &RgbImage) -> Vec<(f32, f32, f32)> {
+    let (w, h) = ink.dimensions();
+    let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+    let max_r = cx.hypot(cy);
+    let mut points = Vec::new();
+    for i in 0..BORDER_RAYS {
+        let theta = i as f32 / BORDER_RAYS as f32 * 2.0 * PI;
+        let (dx, dy) = (theta.cos(), theta.sin());
+        let mut r = max_r;
+        while r > 0.0 {
+            let (x, y) = (cx + dx * r, cy + dy * r);
+            let inside = x >= 0.0 && y >= 0.0 && (x as u32) < w && (y as u32) < h;
+            if inside && is_ink(ink.get_pixel(x as u32, y as u32)) {
+                points.push((x, y, r));
+                break;
+            }
+            r -= 1.0;
+        }
+    }
+    points
+}
+
+fn fit_extent(points: &[(f32, f32)]) -> Ellipse {
+    let (mut x1, mut y1, mut x2, mut y2) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for (x, y) in points.iter() {
+        x1 = x1.min(*x);
+        y1 = y1.min(*y);
+        x2 = x2.max(*x);
+        y2 = y2.max(*y);
+    }
+    Ellipse {
+        cx: (x1 + x2) / 2.0,
+        cy: (y1 + y2) / 2.0,
+        rx: ((x2 - x1) / 2.0).max(1.0),
+        ry: ((y2 - y1) / 2.0).max(1.0),
+    }
+}
+
+// the box around all the ink, the crop center when there is none
+fn ink_extent(ink: &RgbImage) -> Ellipse {
+    let (w, h) = ink.dimensions();
+    let (mut x1, mut y1, mut x2, mut y2) = (w, h, 0, 0);
+    for (x, y, p) in ink.enumerate_pixels() {
+        if is_ink(p) {
+            x1 = x1.min(x);
+            y1 = y1.min(y);
+            x2 = x2.max(x);
+            y2 = y2.max(y);
+        }
+    }
+    if x2 <= x1 || y2 <= y1 {
+        (x1, y1, x2, y2) = (0, 0, w.saturating_sub(1), h.saturating_sub(1));
+    }
+    Ellipse {
+        cx: (x1 + x2) as f32 / 2.0,
+        cy: (y1 + y2) as f32 / 2.0,
+        rx: ((x2 - x1) as f32 / 2.0).max(1.0),
+        ry: ((y2 - y1) as f32 / 2.0).max(1.0),
+    }
+}
+
+// unwrap the ring band clockwise starting at the bottom, where seals leave a gap in the text.
+// the outer border is at the top so the letters stand upright
+fn polar_unwrap(img: &RgbImage, ellipse: Ellipse) -> RgbImage {
+    let radius = (ellipse.rx + ellipse.ry) / 2.0;
+    let height = ((1.0 - RING_INNER) * radius).round().max(8.0) as u32;
+    let width = (PI * (1.0 + RING_INNER) * radius).round().max(8.0) as u32;
+    let mut strip = RgbImage::new(width, height);
+    warp_into_with(
+        img,
+        |x, y| {
+            let theta = PI / 2.0 + x / width as f32 * 2.0 * PI;
+            let scale = 1.0 - y / height as f32 * (1.0 - RING_INNER);
+            (
+                ellipse.cx + ellipse.rx * scale * theta.cos(),
+                ellipse.cy + ellipse.ry * scale * theta.sin(),
+            )
+        },
+        Interpolation::Bilinear,
+        Rgb([255, 255, 255]),
+        &mut strip,
+    );
+    strip
+}
+
+// the seal with its ring band painted white, only the horizontal inner text is left
+fn clear_ring(img: &RgbImage, ellipse: Ellipse) -> RgbImage {
+    let mut inner = img.to_owned();
+    for (x, y, p) in inner.enumerate_pixels_mut() {
+        if ellipse.distance(x as f32, y as f32) > RING_INNER * 0.95 {
+            *p = Rgb([255, 255, 255]);
+        }
+    }
+    inner
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use imageproc::drawing::{draw_filled_rect_mut, draw_hollow_ellipse_mut};
+    use imageproc::rect::Rect;
+
+    fn seal(stray: bool) -> RgbImage {
+        let mut img = RgbImage::from_pixel(200, 160, Rgb([255, 255, 255]));
+        for width in 0..3 {
+            draw_hollow_ellipse_mut(&mut img, (100, 80), 70 - width, 55 - width, Rgb([0, 0, 0]));
+        }
+        if stray {
+            // text clipped from behind the stamp
+            draw_filled_rect_mut(&mut img, Rect::at(0, 0).of_size(60, 12), Rgb([0, 0, 0]));
+            draw_filled_rect_mut(&mut img, Rect::at(170, 140).of_size(30, 20), Rgb([0, 0, 0]));
+        }
+        img
+    }
+
+    fn assert_close(ellipse: Ellipse, expected: [f32; 4]) {
+        let found = [ellipse.cx, ellipse.cy, ellipse.rx, ellipse.ry];
+        for (a, b) in found.iter().zip(expected.iter()) {
+            assert!((a - b).abs() <= 3.0, "{:?} != {:?}", found, expected);
+        }
+    }
+
+    #[test]
+    fn ellipse_follows_the_border() {
+        assert_close(find_ellipse(&seal(false)), [100.0, 80.0, 70.0, 55.0]);
+    }
+
+    #[test]
+    fn ellipse_ignores_stray_ink() {
+        assert_close(find_ellipse(&seal(true)), [100.0, 80.0, 70.0, 55.0]);
+    }
+
+    #[test]
+    fn ellipse_without_border_covers_the_crop() {
+        let img = RgbImage::from_pixel(40, 20, Rgb([255, 255, 255]));
+        assert_close(find_ellipse(&img), [19.5, 9.5, 19.5, 9.5]);
+    }
+}
@@ -1,20 +1,32 @@
 use core::f32;
 
+use std::collections::HashSet;
+
 use anyhow::Result;
 use image::{
-    RgbImage,
+    Rgb, RgbImage, imageops,
     imageops::{crop_imm, rotate90, rotate180, rotate270},
 };
 use itertools::Itertools;
 
+use imageproc::point::Point;
+
 use crate::{
+    common::quad::Quad,
     doc_text_ori::predictor::RotateAngle,
     model_context::ModelContext,
-    pipeline::ocr::{self, OcrResultItem},
+    pipeline::{
+        ocr::{self, LanguageRouting, OcrResultItem},
+        ruled_table::{RuledCell, detect_ruled_cells},
+    },
     table_cell_detection::predictor::TableCelltResult,
     table_cls::predictor::TableType,
+    table_structure::predictor::TableStructure,
 };
 
+// cell crops detected in one batch, they are padded to the largest crop of the batch
+const CELL_BATCH_SIZE: usize = 16;
+
 #[derive(Debug, Clone)]
 pub struct TableCell {
     pub coordinate: [f32; 4],
@@ -24,6 +36,10 @@ pub struct TableCell {
     pub col: u32,
     pub col_span: u32,
     pub row_span: u32,
+    pub is_header: bool,
+    pub text_score: f32,
+    // a table drawn inside this cell
+    pub nested: Option<Box<TableResult>>,
 }
 
 impl TableCell {
@@ -36,235 +52,1381 @@ impl TableCell {
             col: 0,
             col_span: 1,
             row_span: 1,
+            is_header: false,
+            text_score: 1.0,
+            nested: None,
+        }
+    }
+
+    // plain text of the cell, a nested table is flattened row by row
+    pub fn text(&self) -> String {
+        match &self.nested {
+            Some(nested) => nested
+                .grid()
+                .iter()
+                .map(|row| row.iter().flatten().map(|c| c.text()).join(" | "))
+                .join("\n"),
+            None => self.content.to_owned(),
+        }
+    }
+
+    // cell detection score times recognition score, cells the structure model placed without
+    // a matching detection only carry the recognition score
+    pub fn confidence(&self) -> f32 {
+        if self.score > 0.0 {
+            self.score * self.text_score
+        } else {
+            self.text_score
         }
     }
 }
 
+// where the cells of wired tables come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WiredCellSource {
+    // the cell detection model
+    #[default]
+    Model,
+    // ruling lines found on the image, falls back to the model when no grid is found
+    Lines,
+    // the model, completed with the line cells it missed
+    CrossCheck,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableOptions {
+    // resolution the page was rendered at, used to scale pixel tolerances
+    pub dpi: Option<f32>,
+    pub wired_cells: WiredCellSource,
+    // how the cell text is routed to the language recognizers, the default language when unset
+    pub routing: Option<LanguageRouting>,
+}
+
 pub fn extract_table(
     context: &ModelContext,
     img: &RgbImage,
     ocr_res: &[OcrResultItem],
+    options: &TableOptions,
 ) -> Result<TableResult> {
+    let routing = options
+        .routing
+        .unwrap_or(LanguageRouting::Fixed(context.default_language()));
     let doc_text_ori_predictor = &context.doc_text_ori_predictor;
     let doc_angle = doc_text_ori_predictor.predict_image(img)?;
-    let mut ocr_res = ocr_res.to_owned();
-    let mut pre_img = img.to_owned();
-    match doc_angle {
-        RotateAngle::R0 => {}
-        RotateAngle::R90 => {
-            pre_img = rotate270(img);
-            ocr_res = ocr::ocr(context, &pre_img)?;
-        }
-        RotateAngle::R180 => {
-            pre_img = rotate180(img);
-            ocr_res = ocr::ocr(context, &pre_img)?;
-        }
-        RotateAngle::R270 => {
-            pre_img = rotate90(img);
-            ocr_res = ocr::ocr(context, &pre_img)?;
+    let pre_img = match doc_angle {
+        RotateAngle::R0 => img.to_owned(),
+        RotateAngle::R90 => rotate270(img),
+        RotateAngle::R180 => rotate180(img),
+        RotateAngle::R270 => rotate90(img),
+    };
+    let (crop_w, crop_h) = (img.width() as f32, img.height() as f32);
+    let ocr_res = match doc_angle {
+        RotateAngle::R0 => ocr_res.to_owned(),
+        // the page detection is reused, only the text is read again in the upright table
+        _ => {
+            let quads: Vec<Quad> = ocr_res
+                .iter()
+                .map(|item| {
+                    item.polys
+                        .map_points(|p| to_table_frame(&doc_angle, crop_w, crop_h, p))
+                })
+                .collect();
+            ocr::recognize_polys(context, &pre_img, quads.as_slice(), routing)?
         }
-    }
+    };
 
     let table_cls_predictor = &context.table_cls_predictor;
     let table_type = table_cls_predictor.predict_image(&pre_img)?;
-    let table_cells_result = match table_type {
-        TableType::Wired => {
-            let predictor = &context.wired_table_cell_predictor;
-            predictor.predict_image(&pre_img)?
+    let (cell_predictor, structure_predictor) = match table_type {
+        TableType::Wired => (
+            &context.wired_table_cell_predictor,
+            &context.wired_table_structure_predictor,
+        ),
+        TableType::Wireless => (
+            &context.wireless_table_cell_predictor,
+            &context.wireless_table_structure_predictor,
+        ),
+    };
+    let wired = matches!(table_type, TableType::Wired);
+    let ruled_cells = match (table_type, options.wired_cells) {
+        (TableType::Wired, WiredCellSource::Lines | WiredCellSource::CrossCheck) => {
+            detect_ruled_cells(&pre_img)
         }
-        TableType::Wireless => {
-            let predictor = &context.wireless_table_cell_predictor;
-            predictor.predict_image(&pre_img)?
+        _ => Vec::new(),
+    };
+
+    let mut nested_groups = Vec::new();
+    let mut table = if options.wired_cells == WiredCellSource::Lines && ruled_cells.len() > 1 {
+        grid_from_ruled_cells(ruled_cells.as_slice())
+    } else {
+        let table_cells_result = cell_predictor.predict_image(&pre_img)?;
+        let mut table_cells_result = cells_det_result_nms(table_cells_result)?;
+        nested_groups = split_nested_cells(&mut table_cells_result);
+        add_missed_ruled_cells(&mut table_cells_result, ruled_cells.as_slice());
+        let structure = structure_predictor.predict_image(&pre_img)?;
+
+        // the structure tokens give the row/col spans, detected cells give the precise boxes,
+        // fall back to a grid guessed from the detected cells when the structure is unusable
+        match grid_from_structure(&structure) {
+            Some(mut table) => {
+                align_structure_cells(&mut table.cells, table_cells_result.as_slice());
+                table
+            }
+            None => grid_from_detected_cells(table_cells_result.as_slice(), options.dpi),
         }
     };
-    let table_cells_result = cells_det_result_nms(table_cells_result)?;
-
-    // 统计有多少行，多少列,
-    // 每行的高度，每列的宽度
-    // 根据上面信息构造 table
-    // TODO match ocr result with table cell, instead of ocr every table cell
-
-    let mut table_cells = Vec::new();
-    for cell in table_cells_result.iter() {
-        let ocr_items = match_ocr_items(&cell.coordinate, ocr_res.as_slice());
-        let mut content = String::new();
-        for item in ocr_items.iter() {
-            content.push_str(item.content.as_str());
-        }
-        // let cell_ocr_items =
-        //let cell_img = crop_imm(img, x1.ceil() as u32, y1.ceil() as u32, width, height).to_image();
-        //let cell_ocr_reslt = ocr::ocr(context, &cell_img)?;
-        //et content = cell_ocr_reslt.iter().map(|v| &v.content).join("\n");
-        let tcell = TableCell::new(cell.coordinate, cell.score, content);
-        table_cells.push(tcell);
+    attach_nested_tables(&mut table, nested_groups, &pre_img, wired, options.dpi);
+    fill_table_content(context, &pre_img, &mut table, ocr_res.as_slice(), routing)?;
+    if table.header_rows == 0 {
+        detect_header_rows(&mut table, &pre_img);
+    }
+    detect_header_cols(&mut table, &pre_img);
+    // cells go back to the frame of the crop that was passed in
+    if !matches!(doc_angle, RotateAngle::R0) {
+        table.map_coordinates(&|[x1, y1, x2, y2]| {
+            let a = to_crop_frame(&doc_angle, crop_w, crop_h, Point::new(x1, y1));
+            let b = to_crop_frame(&doc_angle, crop_w, crop_h, Point::new(x2, y2));
+            [a.x.min(b.x), a.y.min(b.y), a.x.max(b.x), a.y.max(b.y)]
+        });
+    }
+    Ok(table)
+}
+
+// detected cells lying inside another detected cell belong to a table nested in that cell,
+// they are taken out of the outer grid and returned with their host box
+fn split_nested_cells(
+    detected: &mut Vec<TableCelltResult>,
+) -> Vec<([f32; 4], Vec<TableCelltResult>)> {
+    let area = |b: &[f32; 4]| (b[2] - b[0]) * (b[3] - b[1]);
+    let mut hosts: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (host_id, host) in detected.iter().enumerate() {
+        let host_area = area(&host.coordinate);
+        let inner: Vec<usize> = detected
+            .iter()
+            .enumerate()
+            .filter(|(id, cell)| {
+                let cell_area = area(&cell.coordinate);
+                *id != host_id
+                    && cell_area > 0.0
+                    && cell_area < host_area * 0.8
+                    && intersection_area(&host.coordinate, &cell.coordinate) / cell_area >= 0.9
+            })
+            .map(|(id, _)| id)
+            .collect();
+        if inner.len() >= 2 {
+            hosts.push((host_id, inner));
+        }
+    }
+    // a table nested twice keeps its cells in the innermost host
+    let all_inner: HashSet<usize> = hosts.iter().flat_map(|(_, inner)| inner.clone()).collect();
+    let mut groups = Vec::new();
+    for (host_id, inner) in hosts.iter() {
+        if all_inner.contains(host_id) {
+            continue;
+        }
+        let cells = inner.iter().map(|id| detected[*id].clone()).collect();
+        groups.push((detected[*host_id].coordinate, cells));
+    }
+    let mut id = 0;
+    detected.retain(|_| {
+        let keep = !all_inner.contains(&id);
+        id += 1;
+        keep
+    });
+    groups
+}
+
+// put the nested grids into the outer cells hosting them; cells of wired tables are also
+// searched for ruling lines of a nested table the detector did not see
+fn attach_nested_tables(
+    table: &mut TableResult,
+    groups: Vec<([f32; 4], Vec<TableCelltResult>)>,
+    img: &RgbImage,
+    wired: bool,
+    dpi: Option<f32>,
+) {
+    for (host, inner) in groups {
+        let best = table
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(id, cell)| (id, compute_iou(&cell.coordinate, &host)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((cell_id, iou)) = best
+            && iou > 0.5
+        {
+            let nested = grid_from_detected_cells(inner.as_slice(), dpi);
+            table.cells[cell_id].nested = Some(Box::new(nested));
+        }
+    }
+    if !wired {
+        return;
+    }
+    for cell in table.cells.iter_mut() {
+        if cell.nested.is_some() {
+            continue;
+        }
+        let [x1, y1, x2, y2] = cell.coordinate;
+        if x2 - x1 < 60.0 || y2 - y1 < 40.0 {
+            continue;
+        }
+        let Some(crop) = crop_cell(img, &cell.coordinate) else {
+            continue;
+        };
+        let ruled_cells = detect_ruled_cells(&crop);
+        if ruled_cells.len() > 1 {
+            let mut nested = grid_from_ruled_cells(ruled_cells.as_slice());
+            nested.translate((x1 + 2.0).max(0.0).floor(), (y1 + 2.0).max(0.0).floor());
+            cell.nested = Some(Box::new(nested));
+        }
+    }
+}
+
+// fill the cells from the ocr items, nested tables get the items inside their host cell
+fn fill_table_content(
+    context: &ModelContext,
+    img: &RgbImage,
+    table: &mut TableResult,
+    ocr_res: &[OcrResultItem],
+    routing: LanguageRouting,
+) -> Result<()> {
+    let mut fallback = fill_cell_content(&mut table.cells, ocr_res);
+    fallback.retain(|id| table.cells[*id].nested.is_none());
+    recognize_cells(context, img, &mut table.cells, fallback.as_slice(), routing)?;
+    for cell in table.cells.iter_mut() {
+        let host = cell.coordinate;
+        if let Some(nested) = cell.nested.as_mut() {
+            let items: Vec<OcrResultItem> = ocr_res
+                .iter()
+                .filter(|item| {
+                    let cx = (item.bbox[0] + item.bbox[2]) / 2.0;
+                    let cy = (item.bbox[1] + item.bbox[3]) / 2.0;
+                    cx >= host[0] && cx <= host[2] && cy >= host[1] && cy <= host[3]
+                })
+                .cloned()
+                .collect();
+            fill_table_content(context, img, nested, items.as_slice(), routing)?;
+            cell.content = String::new();
+            cell.text_score = 1.0;
+        }
+    }
+    Ok(())
+}
+
+// a point of the `w` x `h` crop in the frame of the crop turned upright
+fn to_table_frame(angle: &RotateAngle, w: f32, h: f32, p: Point<f32>) -> Point<f32> {
+    match angle {
+        RotateAngle::R0 => p,
+        RotateAngle::R90 => Point::new(p.y, w - p.x),
+        RotateAngle::R180 => Point::new(w - p.x, h - p.y),
+        RotateAngle::R270 => Point::new(h - p.y, p.x),
+    }
+}
+
+fn to_crop_frame(angle: &RotateAngle, w: f32, h: f32, p: Point<f32>) -> Point<f32> {
+    match angle {
+        RotateAngle::R0 => p,
+        RotateAngle::R90 => Point::new(w - p.y, p.x),
+        RotateAngle::R180 => Point::new(w - p.x, h - p.y),
+        RotateAngle::R270 => Point::new(p.y, h - p.x),
+    }
+}
+
+// a table without geometry from rows of texts, short rows are padded with empty cells
+pub fn table_from_rows(rows: &[Vec<String>], header_rows: u32) -> TableResult {
+    let col_count = rows.iter().map(|r| r.len()).max().unwrap_or(0) as u32;
+    let mut cells = Vec::new();
+    for (row, texts) in rows.iter().enumerate() {
+        for col in 0..col_count {
+            let content = texts.get(col as usize).cloned().unwrap_or_default();
+            let mut cell = TableCell::new([0.0; 4], 1.0, content);
+            cell.row = row as u32;
+            cell.col = col;
+            cell.is_header = (row as u32) < header_rows;
+            cells.push(cell);
+        }
+    }
+    TableResult {
+        cells,
+        col_count,
+        row_count: rows.len() as u32,
+        header_rows: header_rows.min(rows.len() as u32),
+        header_cols: 0,
+    }
+}
+
+fn grid_from_ruled_cells(ruled_cells: &[RuledCell]) -> TableResult {
+    let cells: Vec<TableCell> = ruled_cells
+        .iter()
+        .map(|c| {
+            let mut cell = TableCell::new(c.coordinate, 1.0, String::new());
+            cell.row = c.row;
+            cell.col = c.col;
+            cell.row_span = c.row_span;
+            cell.col_span = c.col_span;
+            cell
+        })
+        .collect();
+    let row_count = cells.iter().map(|c| c.row + c.row_span).max().unwrap_or(0);
+    let col_count = cells.iter().map(|c| c.col + c.col_span).max().unwrap_or(0);
+    TableResult {
+        cells,
+        col_count,
+        row_count,
+        header_rows: 0,
+        header_cols: 0,
+    }
+}
+
+// the detector sometimes misses cells of ruled tables, add the line cells no detection covers
+fn add_missed_ruled_cells(detected: &mut Vec<TableCelltResult>, ruled_cells: &[RuledCell]) {
+    for cell in ruled_cells.iter() {
+        let covered = detected
+            .iter()
+            .any(|det| compute_iou(&det.coordinate, &cell.coordinate) > 0.5);
+        if !covered {
+            detected.push(TableCelltResult {
+                label: "cell".to_string(),
+                coordinate: cell.coordinate,
+                score: 1.0,
+            });
+        }
+    }
+}
+
+fn grid_from_structure(structure: &TableStructure) -> Option<TableResult> {
+    let mut cells: Vec<TableCell> = Vec::new();
+    let mut occupied: HashSet<(u32, u32)> = HashSet::new();
+    let mut bboxs = structure.bboxs.iter();
+    let mut row: Option<u32> = None;
+    let mut col: u32 = 0;
+    let mut pending: Option<TableCell> = None;
+    let mut in_thead = false;
+    for token in structure.table_labels.iter() {
+        match token.as_str() {
+            "<thead>" => in_thead = true,
+            "</thead>" => in_thead = false,
+            "<tr>" => {
+                if let Some(cell) = pending.take() {
+                    place_cell(&mut cells, &mut occupied, cell, row.unwrap_or(0), &mut col);
+                }
+                row = Some(row.map_or(0, |r| r + 1));
+                col = 0;
+            }
+            "<td>" | "<td></td>" | "<td" => {
+                if let Some(cell) = pending.take() {
+                    place_cell(&mut cells, &mut occupied, cell, row.unwrap_or(0), &mut col);
+                }
+                let bbox = bboxs.next().map(|b| structure_bbox(b)).unwrap_or([0.0; 4]);
+                let mut cell = TableCell::new(bbox, 0.0, String::new());
+                cell.is_header = in_thead;
+                if token == "<td" {
+                    pending = Some(cell);
+                } else {
+                    place_cell(&mut cells, &mut occupied, cell, row.unwrap_or(0), &mut col);
+                }
+            }
+            ">" => {
+                if let Some(cell) = pending.take() {
+                    place_cell(&mut cells, &mut occupied, cell, row.unwrap_or(0), &mut col);
+                }
+            }
+            attr => {
+                if let Some(cell) = pending.as_mut() {
+                    if let Some(span) = parse_span_attr(attr, "colspan") {
+                        cell.col_span = span;
+                    } else if let Some(span) = parse_span_attr(attr, "rowspan") {
+                        cell.row_span = span;
+                    }
+                }
+            }
+        }
+    }
+    if let Some(cell) = pending.take() {
+        place_cell(&mut cells, &mut occupied, cell, row.unwrap_or(0), &mut col);
     }
+    if cells.is_empty() {
+        return None;
+    }
+    let row_count = cells.iter().map(|c| c.row + c.row_span).max().unwrap_or(0);
+    let col_count = cells.iter().map(|c| c.col + c.col_span).max().unwrap_or(0);
+    let header_rows = cells
+        .iter()
+        .filter(|c| c.is_header)
+        .map(|c| c.row + c.row_span)
+        .max()
+        .unwrap_or(0);
+    cells.sort_by(|a, b| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
+    Some(TableResult {
+        cells,
+        col_count,
+        row_count,
+        header_rows,
+        header_cols: 0,
+    })
+}
+
+// put the cell at the first free column of the row, columns covered by rowspans from the
+// rows above are skipped
+fn place_cell(
+    cells: &mut Vec<TableCell>,
+    occupied: &mut HashSet<(u32, u32)>,
+    mut cell: TableCell,
+    row: u32,
+    col: &mut u32,
+) {
+    while occupied.contains(&(row, *col)) {
+        *col += 1;
+    }
+    cell.row = row;
+    cell.col = *col;
+    for r in row..row + cell.row_span {
+        for c in *col..*col + cell.col_span {
+            occupied.insert((r, c));
+        }
+    }
+    *col += cell.col_span;
+    cells.push(cell);
+}
+
+// ` colspan="2"` -> 2
+fn parse_span_attr(token: &str, name: &str) -> Option<u32> {
+    let value = token
+        .trim()
+        .strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('=')?;
+    let span = value.trim().trim_matches('"').parse::<u32>().ok()?;
+    Some(span.max(1))
+}
+
+fn structure_bbox(bbox: &[u32]) -> [f32; 4] {
+    let xs = bbox.iter().step_by(2).map(|v| *v as f32);
+    let ys = bbox.iter().skip(1).step_by(2).map(|v| *v as f32);
+    let (x1, x2) = xs.fold((f32::INFINITY, 0.0_f32), |(a, b), v| (a.min(v), b.max(v)));
+    let (y1, y2) = ys.fold((f32::INFINITY, 0.0_f32), |(a, b), v| (a.min(v), b.max(v)));
+    if x1 > x2 || y1 > y2 {
+        return [0.0; 4];
+    }
+    [x1, y1, x2, y2]
+}
+
+// replace the regressed structure boxes with the matching detected cell boxes
+fn align_structure_cells(cells: &mut [TableCell], detected: &[TableCelltResult]) {
+    let mut pairs = Vec::new();
+    for (cell_id, cell) in cells.iter().enumerate() {
+        for (det_id, det) in detected.iter().enumerate() {
+            let iou = compute_iou(&cell.coordinate, &det.coordinate);
+            if iou > 0.3 {
+                pairs.push((iou, cell_id, det_id));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut used_cells = HashSet::new();
+    let mut used_dets = HashSet::new();
+    for (_, cell_id, det_id) in pairs {
+        if used_cells.contains(&cell_id) || used_dets.contains(&det_id) {
+            continue;
+        }
+        cells[cell_id].coordinate = detected[det_id].coordinate;
+        cells[cell_id].score = detected[det_id].score;
+        used_cells.insert(cell_id);
+        used_dets.insert(det_id);
+    }
+}
+
+fn grid_from_detected_cells(detected: &[TableCelltResult], dpi: Option<f32>) -> TableResult {
+    let mut table_cells: Vec<TableCell> = detected
+        .iter()
+        .filter(|cell| {
+            cell.coordinate[2] > cell.coordinate[0] && cell.coordinate[3] > cell.coordinate[1]
+        })
+        .map(|cell| TableCell::new(cell.coordinate, cell.score, String::new()))
+        .collect();
     if table_cells.is_empty() {
-        return Ok(TableResult {
+        return TableResult {
             cells: table_cells,
             row_count: 0,
             col_count: 0,
-        });
+            header_rows: 0,
+            header_cols: 0,
+        };
+    }
+
+    let col_ranges: Vec<(f32, f32)> = table_cells
+        .iter()
+        .map(|c| (c.coordinate[0], c.coordinate[2]))
+        .collect();
+    let col_lines = grid_lines(col_ranges.as_slice(), grid_tolerance(&col_ranges, dpi));
+    let row_ranges: Vec<(f32, f32)> = table_cells
+        .iter()
+        .map(|c| (c.coordinate[1], c.coordinate[3]))
+        .collect();
+    let row_lines = grid_lines(row_ranges.as_slice(), grid_tolerance(&row_ranges, dpi));
+
+    for cell in table_cells.iter_mut() {
+        let (col, col_span) = snap_to_grid(&col_lines, cell.coordinate[0], cell.coordinate[2]);
+        let (row, row_span) = snap_to_grid(&row_lines, cell.coordinate[1], cell.coordinate[3]);
+        cell.col = col;
+        cell.col_span = col_span;
+        cell.row = row;
+        cell.row_span = row_span;
+    }
+    table_cells.sort_by(|a, b| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
+
+    TableResult {
+        cells: table_cells,
+        row_count: (row_lines.len() as u32 - 1).max(1),
+        col_count: (col_lines.len() as u32 - 1).max(1),
+        header_rows: 0,
+        header_cols: 0,
+    }
+}
+
+// edges closer than this belong to the same grid line: a fraction of the typical cell extent
+// along the axis (widths for column lines, heights for row lines), never below two points at the
+// render dpi so scan noise does not split lines
+fn grid_tolerance(ranges: &[(f32, f32)], dpi: Option<f32>) -> f32 {
+    let mut sizes: Vec<f32> = ranges.iter().map(|(start, end)| end - start).collect();
+    sizes.sort_by(|a, b| a.total_cmp(b));
+    let median_size = sizes[sizes.len() / 2];
+    let min_tolerance = dpi.map_or(2.0, |dpi| 2.0 * dpi / 72.0);
+    (median_size * 0.3).max(min_tolerance)
+}
+
+// cluster the cell edges of one axis into grid lines, lines only separated by the gap between
+// two cells (no cell spans that interval) are merged
+fn grid_lines(ranges: &[(f32, f32)], tolerance: f32) -> Vec<f32> {
+    let mut edges: Vec<f32> = ranges
+        .iter()
+        .flat_map(|(start, end)| [*start, *end])
+        .collect();
+    edges.sort_by(|a, b| a.total_cmp(b));
+    let mut lines: Vec<f32> = Vec::new();
+    let mut cluster: Vec<f32> = Vec::new();
+    for edge in edges {
+        if let Some(last) = cluster.last()
+            && edge - last > tolerance
+        {
+            lines.push(cluster.iter().sum::<f32>() / cluster.len() as f32);
+            cluster.clear();
+        }
+        cluster.push(edge);
+    }
+    if !cluster.is_empty() {
+        lines.push(cluster.iter().sum::<f32>() / cluster.len() as f32);
     }
 
-    // TODO update cell row id an col_span
-
-    table_cells.sort_by(|a, b| a.coordinate[0].total_cmp(&b.coordinate[0]));
-    let all_x: Vec<f32> = table_cells.iter().map(|c| c.coordinate[0]).collect();
-    let last_x = table_cells.last().unwrap().coordinate[2];
-    let (col_ids, col_width) = calc_table_info(all_x, last_x);
-    for (i, cell) in table_cells.iter_mut().enumerate() {
-        let x = cell.coordinate[0];
-        let max_x = cell.coordinate[2];
-        let cell_w = max_x - x;
-        let col_id = col_ids[i];
-        let mut total_w = col_width[col_id as usize];
-        let mut col_span = 1;
-        while (total_w - cell_w).abs() > 10.0 && (x + total_w) < max_x {
-            total_w += col_width[(col_id + col_span) as usize];
-            col_span += 1;
-        }
-        cell.col = col_id;
-        if col_span > 1 {
-            cell.col_span = col_span
-        }
-    }
-
-    table_cells.sort_by(|a, b| a.coordinate[1].total_cmp(&b.coordinate[1]));
-    let all_y: Vec<f32> = table_cells.iter().map(|c| c.coordinate[1]).collect();
-    let last_y = table_cells.last().unwrap().coordinate[3];
-    let (row_ids, row_height) = calc_table_info(all_y, last_y);
-    for (i, cell) in table_cells.iter_mut().enumerate() {
-        let y = cell.coordinate[1];
-        let max_y = cell.coordinate[3];
-        let cell_h = max_y - y;
-        let row_id = row_ids[i];
-        let mut total_h = row_height[row_id as usize];
-        let mut row_span = 1;
-        while (total_h - cell_h).abs() > 10.0 && (y + total_h) < max_y {
-            total_h += row_height[(row_id + row_span) as usize];
-            row_span += 1;
-        }
-        cell.row = row_id;
-        if row_span > 1 {
-            cell.row_span = row_span;
-        }
-    }
-    table_cells.sort_by(|a, b| {
-        if a.row != b.row {
-            a.row.cmp(&b.row)
+    let mut i = 0;
+    while i + 1 < lines.len() {
+        let mid = (lines[i] + lines[i + 1]) / 2.0;
+        let covered = ranges.iter().any(|(start, end)| *start < mid && *end > mid);
+        if covered {
+            i += 1;
         } else {
-            a.col.cmp(&b.col)
+            lines[i] = mid;
+            lines.remove(i + 1);
         }
-    });
+    }
+    lines
+}
 
-    let table = TableResult {
-        cells: table_cells,
-        row_count: row_height.len() as u32,
-        col_count: col_width.len() as u32,
+// (index, span) of the grid interval a cell covers, edges snap to the nearest grid line
+fn snap_to_grid(lines: &[f32], start: f32, end: f32) -> (u32, u32) {
+    let nearest = |v: f32| {
+        lines
+            .iter()
+            .enumerate()
+            .min_by(|a, b| (a.1 - v).abs().total_cmp(&(b.1 - v).abs()))
+            .map(|(i, _)| i)
+            .unwrap_or(0)
     };
+    let last = lines.len().saturating_sub(2);
+    let first = nearest(start).min(last);
+    let span = nearest(end).saturating_sub(first).max(1);
+    (first as u32, span as u32)
+}
 
-    Ok(table)
+// every ocr item goes to the cell it overlaps most, so text is never duplicated in
+// neighbouring cells. returns the cells to recognize again from their own crop: empty ones and
+// the ones whose ocr lines cross the cell border
+fn fill_cell_content(cells: &mut [TableCell], ocr_res: &[OcrResultItem]) -> Vec<usize> {
+    let mut cell_items: Vec<Vec<&OcrResultItem>> = vec![Vec::new(); cells.len()];
+    let mut partial = vec![false; cells.len()];
+    for item in ocr_res.iter() {
+        let mut best: Option<(usize, f32)> = None;
+        for (cell_id, cell) in cells.iter().enumerate() {
+            let inter = intersection_area(&cell.coordinate, &item.bbox);
+            if inter <= 0.0 {
+                continue;
+            }
+            if best.is_none_or(|(_, area)| inter > area) {
+                best = Some((cell_id, inter));
+            }
+        }
+        if let Some((cell_id, inter)) = best {
+            let [x1, y1, x2, y2] = item.bbox;
+            let item_area = (x2 - x1) * (y2 - y1);
+            if item_area > 0.0 && inter / item_area < 0.7 {
+                partial[cell_id] = true;
+            }
+            cell_items[cell_id].push(item);
+        }
+    }
+    let mut fallback = Vec::new();
+    for (cell_id, (cell, items)) in cells.iter_mut().zip(cell_items).enumerate() {
+        if items.is_empty() || partial[cell_id] {
+            fallback.push(cell_id);
+        }
+        let (content, text_score) = join_cell_lines(items.as_slice());
+        cell.content = content;
+        cell.text_score = text_score;
+    }
+    fallback
+}
+
+// ocr the crop of every cell in `cell_ids`, keeping the page level text when the crop gives
+// nothing better
+fn recognize_cells(
+    context: &ModelContext,
+    img: &RgbImage,
+    cells: &mut [TableCell],
+    cell_ids: &[usize],
+    routing: LanguageRouting,
+) -> Result<()> {
+    let mut crop_ids = Vec::new();
+    let mut crops = Vec::new();
+    for cell_id in cell_ids.iter() {
+        if let Some(crop) = crop_cell(img, &cells[*cell_id].coordinate) {
+            crop_ids.push(*cell_id);
+            crops.push(crop);
+        }
+    }
+    let padded: Vec<RgbImage> = crops.iter().map(|crop| pad_image(crop, 8)).collect();
+    let mut detections = Vec::with_capacity(padded.len());
+    for batch in padded.chunks(CELL_BATCH_SIZE) {
+        detections.extend(context.text_det_predictor.predict_batch(batch)?);
+    }
+
+    let mut undetected = Vec::new();
+    let mut undetected_imgs = Vec::new();
+    for (((cell_id, crop), padded), detection) in crop_ids
+        .into_iter()
+        .zip(crops)
+        .zip(padded.iter())
+        .zip(detections)
+    {
+        let cell = &mut cells[cell_id];
+        let items = ocr::recognize_polys(context, padded, detection.polys.as_slice(), routing)?;
+        let items: Vec<&OcrResultItem> = items
+            .iter()
+            .filter(|item| item.score >= 0.5 && !item.content.trim().is_empty())
+            .collect();
+        if !items.is_empty() {
+            // the page level text stays when it was read with more confidence
+            let (content, text_score) = join_cell_lines(items.as_slice());
+            if cell.content.is_empty() || text_score > cell.text_score {
+                (cell.content, cell.text_score) = (content, text_score);
+            }
+        } else if cell.content.is_empty() {
+            undetected.push(cell_id);
+            undetected_imgs.push(crop);
+        }
+    }
+    // the detector misses very short text such as a single digit, read the whole crop as a line
+    if undetected.is_empty() {
+        return Ok(());
+    }
+    let texts = ocr::recognize(context, undetected_imgs, routing)?;
+    for (cell_id, text) in undetected.into_iter().zip(texts) {
+        let content = text.content.trim();
+        if text.score >= 0.5 && !content.is_empty() {
+            cells[cell_id].content = content.to_string();
+            cells[cell_id].text_score = text.score;
+        }
+    }
+    Ok(())
+}
+
+// group the items of a cell into lines, lines are joined with line breaks; the score is the
+// mean item score weighted by text length
+pub(crate) fn join_cell_lines(items: &[&OcrResultItem]) -> (String, f32) {
+    let mut sorted = items.to_vec();
+    sorted.sort_by(|a, b| (a.bbox[1] + a.bbox[3]).total_cmp(&(b.bbox[1] + b.bbox[3])));
+    let mut lines: Vec<Vec<&OcrResultItem>> = Vec::new();
+    for item in sorted {
+        let [_, y1, _, y2] = item.bbox;
+        if let Some(line) = lines.last_mut() {
+            let ly1 = line.iter().map(|i| i.bbox[1]).fold(f32::INFINITY, f32::min);
+            let ly2 = line.iter().map(|i| i.bbox[3]).fold(0.0, f32::max);
+            let overlap = y2.min(ly2) - y1.max(ly1);
+            if overlap >= (y2 - y1).min(ly2 - ly1) * 0.5 {
+                line.push(item);
+                continue;
+            }
+        }
+        lines.push(vec![item]);
+    }
+    let mut content = Vec::new();
+    for line in lines.iter_mut() {
+        line.sort_by(|a, b| a.bbox[0].total_cmp(&b.bbox[0]));
+        let mut text = String::new();
+        for item in line.iter() {
+            let part = item.content.trim();
+            if part.is_empty() {
+                continue;
+            }
+            // cjk text runs on without spaces
+            let cjk = text.chars().last().is_some_and(|c| !c.is_ascii())
+                && part.chars().next().is_some_and(|c| !c.is_ascii());
+            if !text.is_empty() && !cjk {
+                text.push(' ');
+            }
+            text.push_str(part);
+        }
+        if !text.is_empty() {
+            content.push(text);
+        }
+    }
+
+    let total: usize = items.iter().map(|i| i.content.chars().count()).sum();
+    let text_score = if total == 0 {
+        1.0
+    } else {
+        items
+            .iter()
+            .map(|i| i.score * i.content.chars().count() as f32)
+            .sum::<f32>()
+            / total as f32
+    };
+    (content.join("\n"), text_score)
+}
+
+// the cell without its borders
+fn crop_cell(img: &RgbImage, bbox: &[f32; 4]) -> Option<RgbImage> {
+    let inset = 2.0;
+    let x1 = (bbox[0] + inset).max(0.0) as u32;
+    let y1 = (bbox[1] + inset).max(0.0) as u32;
+    let x2 = ((bbox[2] - inset).max(0.0) as u32).min(img.width());
+    let y2 = ((bbox[3] - inset).max(0.0) as u32).min(img.height());
+    if x2 < x1 + 4 || y2 < y1 + 4 {
+        return None;
+    }
+    Some(crop_imm(img, x1, y1, x2 - x1, y2 - y1).to_image())
+}
+
+// white margin around a crop so the text detector sees the text edges
+fn pad_image(img: &RgbImage, pad: u32) -> RgbImage {
+    let mut padded = RgbImage::from_pixel(
+        img.width() + pad * 2,
+        img.height() + pad * 2,
+        Rgb([255, 255, 255]),
+    );
+    imageops::replace(&mut padded, img, pad as i64, pad as i64);
+    padded
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TableFormat {
+    #[default]
+    Html,
+    Markdown,
+    Csv,
+    Json,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TableResult {
     cells: Vec<TableCell>,
     col_count: u32,
     row_count: u32,
+    header_rows: u32,
+    header_cols: u32,
 }
 
 impl TableResult {
+    pub fn cells(&self) -> &[TableCell] {
+        self.cells.as_slice()
+    }
+
+    pub fn row_count(&self) -> u32 {
+        self.row_count
+    }
+
+    pub fn col_count(&self) -> u32 {
+        self.col_count
+    }
+
+    pub fn header_rows(&self) -> u32 {
+        self.header_rows
+    }
+
+    pub fn header_cols(&self) -> u32 {
+        self.header_cols
+    }
+
+    // a table cut by a page break is continued by `next` when both have the same columns, the
+    // widths are compared relative to the table width as the pages may be rendered differently
+    pub fn is_continued_by(&self, next: &TableResult) -> bool {
+        if self.cells.is_empty() || next.cells.is_empty() || self.col_count != next.col_count {
+            return false;
+        }
+        let widths = self.column_widths();
+        let next_widths = next.column_widths();
+        let mut compared = 0;
+        for (a, b) in widths.iter().zip(next_widths.iter()) {
+            if let (Some(a), Some(b)) = (a, b) {
+                if (a - b).abs() > 0.05 {
+                    return false;
+                }
+                compared += 1;
+            }
+        }
+        compared > 0 || self.repeated_header_rows(next) > 0
+    }
+
+    // append the rows of the continuation, headers it repeats are dropped
+    pub fn merge(&mut self, next: TableResult) {
+        let skip = self.repeated_header_rows(&next);
+        let offset = self.row_count;
+        for mut cell in next.cells {
+            if cell.row + cell.row_span <= skip {
+                continue;
+            }
+            // a cell spanning from the repeated header into the body keeps its body rows
+            if cell.row < skip {
+                cell.row_span -= skip - cell.row;
+                cell.row = skip;
+            }
+            // only header column cells stay headers in the body
+            cell.is_header = cell.is_header && cell.row >= next.header_rows;
+            cell.row = cell.row - skip + offset;
+            self.cells.push(cell);
+        }
+        self.row_count += next.row_count - skip;
+        self.col_count = self.col_count.max(next.col_count);
+        self.header_cols = self.header_cols.max(next.header_cols);
+    }
+
+    // width of every column as a fraction of the table width, measured on unmerged cells
+    fn column_widths(&self) -> Vec<Option<f32>> {
+        let x1 = self
+            .cells
+            .iter()
+            .map(|c| c.coordinate[0])
+            .fold(f32::INFINITY, f32::min);
+        let x2 = self
+            .cells
+            .iter()
+            .map(|c| c.coordinate[2])
+            .fold(0.0, f32::max);
+        let total = x2 - x1;
+        (0..self.col_count)
+            .map(|col| {
+                let mut widths: Vec<f32> = self
+                    .cells
+                    .iter()
+                    .filter(|c| c.col == col && c.col_span == 1)
+                    .map(|c| c.coordinate[2] - c.coordinate[0])
+                    .collect();
+                if widths.is_empty() || total <= 0.0 {
+                    return None;
+                }
+                widths.sort_by(|a, b| a.total_cmp(b));
+                Some(widths[widths.len() / 2] / total)
+            })
+            .collect()
+    }
+
+    // leading rows of `next` repeating the header of this table
+    fn repeated_header_rows(&self, next: &TableResult) -> u32 {
+        // the header flags of the continuation are not trusted, its first data row is often
+        // taken for a header, only rows repeating the header text are dropped
+        let rows = self.header_rows.max(1);
+        if rows > next.row_count {
+            return 0;
+        }
+        let repeated = (0..rows).all(|row| {
+            let text = self.row_text(row);
+            !text.is_empty() && text == next.row_text(row)
+        });
+        if repeated { rows } else { 0 }
+    }
+
+    // text of one row without whitespace, for comparing repeated rows
+    fn row_text(&self, row: u32) -> String {
+        self.cells
+            .iter()
+            .filter(|c| c.row == row)
+            .sorted_by_key(|c| c.col)
+            .map(|c| c.content.split_whitespace().join(""))
+            .join("|")
+            .trim_matches('|')
+            .to_string()
+    }
+
+    // move the cells by the origin of the table crop to get page coordinates
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.map_coordinates(&|[x1, y1, x2, y2]| [x1 + dx, y1 + dy, x2 + dx, y2 + dy]);
+    }
+
+    fn map_coordinates(&mut self, f: &dyn Fn([f32; 4]) -> [f32; 4]) {
+        for cell in self.cells.iter_mut() {
+            cell.coordinate = f(cell.coordinate);
+            if let Some(nested) = cell.nested.as_mut() {
+                nested.map_coordinates(f);
+            }
+        }
+    }
+
+    pub fn has_nested(&self) -> bool {
+        self.cells.iter().any(|c| c.nested.is_some())
+    }
+
+    pub fn has_span(&self) -> bool {
+        self.cells.iter().any(|c| c.row_span > 1 || c.col_span > 1)
+    }
+
+    pub fn render(&self, format: TableFormat) -> String {
+        match format {
+            TableFormat::Html => self.to_html(),
+            TableFormat::Markdown => self.to_markdown(),
+            TableFormat::Csv => self.to_csv(),
+            TableFormat::Json => self.to_json(),
+        }
+    }
+
+    // row major grid, a merged cell is only placed at its top left position
+    fn grid(&self) -> Vec<Vec<Option<&TableCell>>> {
+        let mut grid = vec![vec![None; self.col_count as usize]; self.row_count as usize];
+        for cell in self.cells.iter() {
+            if let Some(slot) = grid
+                .get_mut(cell.row as usize)
+                .and_then(|row| row.get_mut(cell.col as usize))
+            {
+                *slot = Some(cell);
+            }
+        }
+        grid
+    }
+
+    // github flavoured markdown, which cannot merge cells, so spanned tables stay html
+    pub fn to_markdown(&self) -> String {
+        if self.cells.is_empty() {
+            return String::new();
+        }
+        // markdown has exactly one header row and no nesting
+        if self.has_span() || self.has_nested() || self.header_rows > 1 {
+            return self.to_html();
+        }
+        let mut markdown = String::new();
+        if self.header_rows == 0 {
+            let empty = vec![" "; self.col_count as usize].join("|");
+            let sep = vec!["---"; self.col_count as usize].join(" | ");
+            markdown.push_str(format!("|{}|\n| {} |\n", empty, sep).as_str());
+        }
+        for (row_id, row) in self.grid().iter().enumerate() {
+            let line = row
+                .iter()
+                .map(|cell| match cell {
+                    Some(c) => {
+                        let content = c.content.trim().replace('|', "\\|").replace('\n', "<br>");
+                        // header column cells are bolded in body rows
+                        if c.is_header && row_id as u32 >= self.header_rows && !content.is_empty() {
+                            format!("**{}**", content)
+                        } else {
+                            content
+                        }
+                    }
+                    None => String::new(),
+                })
+                .join(" | ");
+            markdown.push_str(format!("| {} |\n", line).as_str());
+            if row_id == 0 && self.header_rows == 1 {
+                let sep = vec!["---"; row.len()].join(" | ");
+                markdown.push_str(format!("| {} |\n", sep).as_str());
+            }
+        }
+        markdown
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for row in self.grid().iter() {
+            let line = row
+                .iter()
+                .map(|cell| csv_field(cell.map(|c| c.text()).unwrap_or_default().as_str()))
+                .join(",");
+            csv.push_str(line.as_str());
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        self.to_json_value().to_string()
+    }
+
+    fn to_json_value(&self) -> serde_json::Value {
+        let cells: Vec<serde_json::Value> = self
+            .cells
+            .iter()
+            .map(|c| {
+                serde_json::json!({
+                    "row": c.row,
+                    "col": c.col,
+                    "rowspan": c.row_span,
+                    "colspan": c.col_span,
+                    "bbox": c.coordinate,
+                    "confidence": c.confidence(),
+                    "header": c.is_header,
+                    "content": c.content,
+                    "nested": c.nested.as_ref().map(|n| n.to_json_value()),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "row_count": self.row_count,
+            "col_count": self.col_count,
+            "header_rows": self.header_rows,
+            "header_cols": self.header_cols,
+            "cells": cells,
+        })
+    }
+
+    // an empty table leaves only the caption, written as markdown emphasis like image captions
+    pub fn to_html_with_caption(&self, caption: &str) -> String {
+        let html = self.to_html();
+        match html.find('>') {
+            Some(pos) => format!(
+                "{}<caption>{}</caption>{}",
+                &html[..=pos],
+                escape_html(caption),
+                &html[pos + 1..]
+            ),
+            None => format!("*{}*", caption),
+        }
+    }
+
     pub fn to_html(&self) -> String {
         if self.cells.is_empty() {
             return String::new();
         }
         let mut html =
-            "<table style=\"  border-collapse: collapse; border: 2px solid black; \"><tbody><tr>"
-                .to_string();
-        let mut current_row = 0;
-        for cell in self.cells.iter() {
-            if cell.row != current_row {
-                current_row = cell.row;
-                html.push_str("</tr><tr style=\"border-bottom: 1px solid black;\">");
-                let td = self.format_cell(cell);
-                html.push_str(td.as_str());
+            "<table style=\"  border-collapse: collapse; border: 2px solid black; \">".to_string();
+        let mut cells = self.cells.iter().peekable();
+        for row in 0..self.row_count {
+            if row == 0 && self.header_rows > 0 {
+                html.push_str("<thead>");
+            }
+            if row == self.header_rows {
+                html.push_str("<tbody>");
+            }
+            // rows fully covered by rowspans still get their own <tr>
+            if row == 0 {
+                html.push_str("<tr>");
             } else {
-                let td = self.format_cell(cell);
-                html.push_str(td.as_str());
+                html.push_str("<tr style=\"border-bottom: 1px solid black;\">");
+            }
+            while let Some(cell) = cells.next_if(|c| c.row == row) {
+                html.push_str(self.format_cell(cell).as_str());
+            }
+            html.push_str("</tr>");
+            if row + 1 == self.header_rows {
+                html.push_str("</thead>");
             }
         }
-        html.push_str("</tr></tbody></table>");
+        if self.header_rows >= self.row_count {
+            html.push_str("<tbody>");
+        }
+        html.push_str("</tbody></table>");
         html
     }
 
     pub fn format_cell(&self, cell: &TableCell) -> String {
+        let tag = if cell.is_header { "th" } else { "td" };
+        let mut attrs = String::new();
+        if cell.col_span > 1 {
+            attrs.push_str(format!(" colspan=\"{}\"", cell.col_span).as_str());
+        }
         if cell.row_span > 1 {
-            if cell.col_span > 1 {
-                format!(
-                    "<td style=\"border: 1px solid gray; padding: 8px;\" colspan=\"{}\" rowspan=\"{}\">{}</td>",
-                    cell.col_span, cell.row_span, cell.content
-                )
-            } else {
-                format!(
-                    "<td  style=\"border: 1px solid gray; padding: 8px;\" rowspan=\"{}\">{}</td>",
-                    cell.row_span, cell.content
-                )
-            }
-        } else {
-            if cell.col_span > 1 {
-                format!(
-                    "<td  style=\"border: 1px solid gray; padding: 8px;\" colspan=\"{}\">{}</td>",
-                    cell.col_span, cell.content
-                )
-            } else {
-                format!(
-                    "<td style=\"border: 1px solid gray; padding: 8px;\" >{}</td>",
-                    cell.content
-                )
-            }
+            attrs.push_str(format!(" rowspan=\"{}\"", cell.row_span).as_str());
+        }
+        let content = match &cell.nested {
+            Some(nested) => nested.to_html(),
+            None => cell.content.replace('\n', "<br>"),
+        };
+        format!(
+            "<{} style=\"border: 1px solid gray; padding: 8px;\"{}>{}</{}>",
+            tag, attrs, content, tag
+        )
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            c => res.push(c),
         }
     }
+    res
 }
 
-fn calc_table_info(all_value: Vec<f32>, last_value: f32) -> (Vec<u32>, Vec<f32>) {
-    let cell_num = all_value.len();
+// without <thead> from the structure the first row is a header when it stands out from the
+// body: text over numeric columns, a shaded background or bold strokes
+fn detect_header_rows(table: &mut TableResult, img: &RgbImage) {
+    if table.row_count < 2 {
+        return;
+    }
+    let first: Vec<&TableCell> = table.cells.iter().filter(|c| c.row == 0).collect();
+    let body: Vec<&TableCell> = table.cells.iter().filter(|c| c.row > 0).collect();
+    if first.is_empty() || body.is_empty() {
+        return;
+    }
+    let is_header = (numeric_ratio(first.as_slice()) == 0.0
+        && numeric_ratio(body.as_slice()) >= 0.3)
+        || stands_out(img, first.as_slice(), body.as_slice());
+    if !is_header {
+        return;
+    }
+    let mut header_rows = first.iter().map(|c| c.row_span).max().unwrap_or(1);
+    // a merged group header is usually followed by its sub headers
+    let has_group = first.iter().any(|c| c.col_span > 1);
+    if has_group && header_rows + 1 < table.row_count {
+        let next: Vec<&TableCell> = table
+            .cells
+            .iter()
+            .filter(|c| c.row == header_rows)
+            .collect();
+        if !next.is_empty() && numeric_ratio(next.as_slice()) == 0.0 {
+            header_rows += 1;
+        }
+    }
+    table.header_rows = header_rows;
+    for cell in table.cells.iter_mut() {
+        if cell.row < header_rows {
+            cell.is_header = true;
+        }
+    }
+}
 
-    let mut row_indexs: Vec<u32> = Vec::with_capacity(cell_num);
-    let mut row_height: Vec<f32> = Vec::with_capacity(cell_num);
+// the first column holds row labels when it is text next to numeric columns, or styled
+// differently from the other body cells
+fn detect_header_cols(table: &mut TableResult, img: &RgbImage) {
+    if table.col_count < 2 {
+        return;
+    }
+    let header_rows = table.header_rows;
+    let first: Vec<&TableCell> = table
+        .cells
+        .iter()
+        .filter(|c| c.row >= header_rows && c.col == 0)
+        .collect();
+    let others: Vec<&TableCell> = table
+        .cells
+        .iter()
+        .filter(|c| c.row >= header_rows && c.col > 0)
+        .collect();
+    if first.len() < 2 || others.is_empty() {
+        return;
+    }
+    let filled = first
+        .iter()
+        .filter(|c| !c.content.trim().is_empty())
+        .count();
+    let is_header = (filled * 2 >= first.len()
+        && numeric_ratio(first.as_slice()) == 0.0
+        && numeric_ratio(others.as_slice()) >= 0.5)
+        || stands_out(img, first.as_slice(), others.as_slice());
+    if !is_header {
+        return;
+    }
+    table.header_cols = 1;
+    for cell in table.cells.iter_mut() {
+        if cell.col == 0 {
+            cell.is_header = true;
+        }
+    }
+}
 
-    let mut current_y: f32 = 0.0;
-    let mut current_row: u32 = 0;
-    let mut tmp_y = Vec::new();
-    for (i, y) in all_value.iter().enumerate() {
-        if i == 0 {
-            current_y = *y;
-            row_indexs.push(current_row);
-            tmp_y.push(current_y);
+// share of the non empty cells holding a number
+fn numeric_ratio(cells: &[&TableCell]) -> f32 {
+    let filled: Vec<&&TableCell> = cells
+        .iter()
+        .filter(|c| !c.content.trim().is_empty())
+        .collect();
+    if filled.is_empty() {
+        return 0.0;
+    }
+    let numeric = filled
+        .iter()
+        .filter(|c| parse_number(c.content.as_str()).is_some())
+        .count();
+    numeric as f32 / filled.len() as f32
+}
+
+fn stands_out(img: &RgbImage, cells: &[&TableCell], others: &[&TableCell]) -> bool {
+    let mean = |values: Vec<f32>| {
+        if values.is_empty() {
+            None
         } else {
-            if (y - current_y).abs() < 10.0 {
-                row_indexs.push(current_row);
-                current_y = *y;
-                tmp_y.push(current_y);
-            } else {
-                let min_y = tmp_y.iter().fold(f32::INFINITY, |a, b| a.min(*b));
-                let height = y - min_y;
-                tmp_y.clear();
-                row_height.push(height);
-
-                current_y = *y;
-                current_row += 1;
-                row_indexs.push(current_row);
-                tmp_y.push(current_y);
+            Some(values.iter().sum::<f32>() / values.len() as f32)
+        }
+    };
+    let background = |cells: &[&TableCell]| {
+        mean(
+            cells
+                .iter()
+                .filter_map(|c| cell_background(img, &c.coordinate))
+                .collect(),
+        )
+    };
+    let stroke = |cells: &[&TableCell]| {
+        mean(
+            cells
+                .iter()
+                .filter_map(|c| stroke_width(img, &c.coordinate))
+                .collect(),
+        )
+    };
+    if let (Some(a), Some(b)) = (background(cells), background(others))
+        && b - a > 15.0
+    {
+        return true;
+    }
+    if let (Some(a), Some(b)) = (stroke(cells), stroke(others))
+        && a > b * 1.25
+    {
+        return true;
+    }
+    false
+}
+
+// cell area without its borders, clamped to the image
+fn cell_pixels(img: &RgbImage, bbox: &[f32; 4]) -> Vec<Vec<f32>> {
+    let inset = 3.0;
+    let x1 = (bbox[0] + inset).max(0.0) as u32;
+    let y1 = (bbox[1] + inset).max(0.0) as u32;
+    let x2 = ((bbox[2] - inset).max(0.0) as u32).min(img.width());
+    let y2 = ((bbox[3] - inset).max(0.0) as u32).min(img.height());
+    (y1..y2)
+        .map(|y| {
+            (x1..x2)
+                .map(|x| {
+                    let [r, g, b] = img.get_pixel(x, y).0;
+                    0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// the background dominates a cell, so its median luminance is the fill colour
+fn cell_background(img: &RgbImage, bbox: &[f32; 4]) -> Option<f32> {
+    let mut values: Vec<f32> = cell_pixels(img, bbox).into_iter().flatten().collect();
+    if values.is_empty() {
+        return None;
+    }
+    let mid = values.len() / 2;
+    let (_, median, _) = values.select_nth_unstable_by(mid, |a, b| a.total_cmp(b));
+    Some(*median)
+}
+
+// mean length of the horizontal ink runs, bold text draws wider strokes
+fn stroke_width(img: &RgbImage, bbox: &[f32; 4]) -> Option<f32> {
+    let pixels = cell_pixels(img, bbox);
+    let (min, max) = pixels
+        .iter()
+        .flatten()
+        .fold((f32::INFINITY, 0.0_f32), |(a, b), v| (a.min(*v), b.max(*v)));
+    // no text in the cell
+    if max - min < 60.0 {
+        return None;
+    }
+    let threshold = (min + max) / 2.0;
+    let mut total = 0;
+    let mut runs = 0;
+    for row in pixels.iter() {
+        let mut run = 0;
+        for v in row.iter().chain(std::iter::once(&f32::INFINITY)) {
+            if *v < threshold {
+                run += 1;
+            } else if run > 0 {
+                total += run;
+                runs += 1;
+                run = 0;
             }
         }
     }
-    if !tmp_y.is_empty() {
-        current_row += 1;
-        row_indexs.push(current_row);
-        let height = last_value - tmp_y.iter().fold(f32::INFINITY, |a, b| a.min(*b));
-        row_height.push(height);
+    if runs == 0 {
+        return None;
+    }
+    Some(total as f32 / runs as f32)
+}
+
+// "1,234.50" -> 1234.5, "(12)" -> -12, "$3" -> 3, "15%" -> 0.15; codes with leading zeros
+// such as "007" stay text
+pub(crate) fn parse_number(content: &str) -> Option<f64> {
+    let mut s = content.trim();
+    let mut negative = false;
+    if let Some(inner) = s.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+        negative = true;
+        s = inner.trim();
+    }
+    if let Some(rest) = s.strip_prefix('-') {
+        negative = !negative;
+        s = rest.trim_start();
+    }
+    s = s
+        .trim_start_matches(['$', '¥', '€', '£', '￥'])
+        .trim_start();
+    let percent = s.ends_with('%');
+    s = s.trim_end_matches('%').trim_end();
+
+    let (int_part, frac_part) = match s.split_once('.') {
+        Some((i, f)) => (i, Some(f)),
+        None => (s, None),
+    };
+    if int_part.is_empty() || !int_part.chars().next()?.is_ascii_digit() {
+        return None;
+    }
+    // thousands separators must group by three
+    let groups: Vec<&str> = int_part.split(',').collect();
+    if groups.len() > 1
+        && (groups[0].is_empty() || groups[0].len() > 3 || groups[1..].iter().any(|g| g.len() != 3))
+    {
+        return None;
+    }
+    let digits: String = groups.concat();
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
     }
-    (row_indexs, row_height)
+    if digits.len() > 1 && digits.starts_with('0') {
+        return None;
+    }
+    if let Some(frac) = frac_part
+        && (frac.is_empty() || !frac.chars().all(|c| c.is_ascii_digit()))
+    {
+        return None;
+    }
+    let number = match frac_part {
+        Some(frac) => format!("{}.{}", digits, frac),
+        None => digits,
+    };
+    let mut value: f64 = number.parse().ok()?;
+    if percent {
+        value /= 100.0;
+    }
+    if negative {
+        value = -value;
+    }
+    Some(value)
 }
 
 fn cells_det_result_nms(cells_det_results: Vec<TableCelltResult>) -> Result<Vec<TableCelltResult>> {
@@ -298,21 +1460,142 @@ fn compute_iou(box1: &[f32; 4], box2: &[f32; 4]) -> f32 {
     iou
 }
 
-fn match_ocr_items(bbox: &[f32; 4], ocr_res: &[OcrResultItem]) -> Vec<OcrResultItem> {
-    let mut res = Vec::new();
-    let [tx1, ty1, tx2, ty2] = bbox;
-    for item in ocr_res.iter() {
-        let [ox1, oy1, ox2, oy2] = &item.bbox;
-        let x1 = ox1.max(*tx1);
-        let y1 = oy1.max(*ty1);
-        let x2 = ox2.min(*tx2);
-        let y2 = oy2.min(*ty2);
-        if x2 <= x1 || y2 <= y1 {
-            continue;
-        }
-        if ((x2 - x1) > 3.0) & ((y2 - y1) > 3.0) {
-            res.push(item.to_owned());
-        }
+// quote fields holding separators, quotes or line breaks as rfc 4180 asks
+fn csv_field(content: &str) -> String {
+    if content.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", content.replace('"', "\"\""))
+    } else {
+        content.to_string()
+    }
+}
+
+fn intersection_area(box1: &[f32; 4], box2: &[f32; 4]) -> f32 {
+    let x1 = box1[0].max(box2[0]);
+    let y1 = box1[1].max(box2[1]);
+    let x2 = box1[2].min(box2[2]);
+    let y2 = box1[3].min(box2[3]);
+    if x2 <= x1 || y2 <= y1 {
+        return 0.0;
+    }
+    (x2 - x1) * (y2 - y1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_tolerance_follows_the_axis_extent() {
+        let cols = [(0.0, 100.0), (100.0, 200.0), (200.0, 300.0)];
+        let rows = [(0.0, 20.0), (0.0, 20.0), (0.0, 20.0)];
+        assert!((grid_tolerance(&cols, None) - 30.0).abs() < 1e-3);
+        assert!((grid_tolerance(&rows, None) - 6.0).abs() < 1e-3);
+        // never below two points at the render dpi
+        assert_eq!(grid_tolerance(&[(0.0, 1.0)], Some(144.0)), 4.0);
+    }
+
+    #[test]
+    fn parse_number_reads_formatted_amounts() {
+        assert_eq!(parse_number("1,234.50"), Some(1234.5));
+        assert_eq!(parse_number("(12)"), Some(-12.0));
+        assert_eq!(parse_number("-3"), Some(-3.0));
+        assert_eq!(parse_number("$3"), Some(3.0));
+        assert_eq!(parse_number("15%"), Some(0.15));
+        assert_eq!(parse_number("0.5"), Some(0.5));
+    }
+
+    #[test]
+    fn parse_number_keeps_codes_and_text() {
+        assert_eq!(parse_number("007"), None);
+        assert_eq!(parse_number("12,34"), None);
+        assert_eq!(parse_number("1.2.3"), None);
+        assert_eq!(parse_number("3."), None);
+        assert_eq!(parse_number("abc"), None);
+        assert_eq!(parse_number(""), None);
+    }
+
+    #[test]
+    fn html_caption_is_escaped() {
+        let table = table_from_rows(&[vec!["a".to_string()]], 0);
+        let html = table.to_html_with_caption("Table 1: x < y & z");
+        assert!(html.contains("<caption>Table 1: x &lt; y &amp; z</caption>"));
+        let empty = table_from_rows(&[], 0);
+        assert_eq!(empty.to_html_with_caption("Table 2"), "*Table 2*");
+    }
+
+    #[test]
+    fn parse_span_attr_reads_the_span() {
+        assert_eq!(parse_span_attr(" colspan=\"2\"", "colspan"), Some(2));
+        assert_eq!(parse_span_attr("rowspan = 3", "rowspan"), Some(3));
+        assert_eq!(parse_span_attr(" colspan=\"0\"", "colspan"), Some(1));
+        assert_eq!(parse_span_attr(" rowspan=\"2\"", "colspan"), None);
+        assert_eq!(parse_span_attr(" colspan=\"x\"", "colspan"), None);
+    }
+
+    fn rows(texts: &[&[&str]]) -> Vec<Vec<String>> {
+        texts
+            .iter()
+            .map(|row| row.iter().map(|t| t.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn merge_drops_repeated_header() {
+        let mut table = table_from_rows(&rows(&[&["Item", "Amount"], &["Rent", "100"]]), 1);
+        let next = table_from_rows(&rows(&[&["Item", "Amount"], &["Power", "20"]]), 1);
+        table.merge(next);
+        assert_eq!(table.row_count, 3);
+        assert_eq!(table.row_text(2), "Power|20");
+    }
+
+    #[test]
+    fn merge_keeps_rows_marked_as_header_on_the_continuation() {
+        let mut table = table_from_rows(&rows(&[&["Item", "Amount"], &["Rent", "100"]]), 1);
+        // the first data row of the next page was taken for a header
+        let next = table_from_rows(&rows(&[&["Power", "20"], &["Water", "5"]]), 1);
+        table.merge(next);
+        assert_eq!(table.row_count, 4);
+        assert_eq!(table.row_text(2), "Power|20");
+        assert_eq!(table.row_text(3), "Water|5");
+        assert!(
+            table
+                .cells
+                .iter()
+                .filter(|c| c.row >= 2)
+                .all(|c| !c.is_header)
+        );
+    }
+
+    #[test]
+    fn merge_clips_spans_reaching_into_the_body() {
+        let mut table = table_from_rows(&rows(&[&["Item", "Amount"], &["Rent", "100"]]), 1);
+        let mut next = table_from_rows(&rows(&[&["Item", "Amount"], &["", "20"]]), 1);
+        next.cells.retain(|c| !(c.row == 1 && c.col == 0));
+        next.cells[0].row_span = 2;
+        table.merge(next);
+        assert_eq!(table.row_count, 3);
+        let clipped = table
+            .cells
+            .iter()
+            .find(|c| c.row == 2 && c.col == 0)
+            .unwrap();
+        assert_eq!(clipped.row_span, 1);
+    }
+
+    #[test]
+    fn grid_lines_cluster_close_edges() {
+        let ranges = [(0.0, 50.0), (52.0, 100.0), (1.0, 99.0)];
+        let lines = grid_lines(&ranges, 5.0);
+        assert_eq!(lines.len(), 3);
+        assert!((lines[0] - 0.5).abs() < 1e-3);
+        assert!((lines[1] - 51.0).abs() < 1e-3);
+        assert!((lines[2] - 99.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn grid_lines_merge_uncovered_gaps() {
+        // the gap between the two cells is wider than the tolerance but no cell spans it
+        let ranges = [(0.0, 50.0), (60.0, 100.0)];
+        assert_eq!(grid_lines(&ranges, 5.0), vec![0.0, 55.0, 100.0]);
     }
-    res
 }
@@ -0,0 +1,356 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::common::zip::ZipWriter;
+use crate::doc_layout::predictor::LayoutLabel;
+use crate::pipeline::layout_parsing::layout_object::LayoutRegion;
+use crate::pipeline::table::{TableResult, parse_number};
+
+// a table with where it was found, one sheet is written per table
+pub struct SheetTable<'a> {
+    pub table: &'a TableResult,
+    pub page: usize,
+    pub bbox: [f32; 4],
+}
+
+impl<'a> SheetTable<'a> {
+    pub fn new(table: &'a TableResult, page: usize, bbox: [f32; 4]) -> Self {
+        SheetTable { table, page, bbox }
+    }
+}
+
+impl TableResult {
+    pub fn to_xlsx(&self) -> Result<Vec<u8>> {
+        let bbox = self
+            .cells()
+            .iter()
+            .map(|c| c.coordinate)
+            .reduce(|a, b| {
+                [
+                    a[0].min(b[0]),
+                    a[1].min(b[1]),
+                    a[2].max(b[2]),
+                    a[3].max(b[3]),
+                ]
+            })
+            .unwrap_or_default();
+        tables_to_xlsx(&[SheetTable::new(self, 0, bbox)])
+    }
+}
+
+// every table of every page, pages are numbered by their position in `pages`
+pub fn document_to_xlsx(pages: &[LayoutRegion]) -> Result<Vec<u8>> {
+    let mut tables = Vec::new();
+    for (page, region) in pages.iter().enumerate() {
+        for block in region.blocks().iter() {
+            if block.label != LayoutLabel::Table {
+                continue;
+            }
+            if let Some(table) = &block.table {
+                tables.push(SheetTable::new(table, page, block.bbox));
+            }
+        }
+    }
+    tables_to_xlsx(tables.as_slice())
+}
+
+pub fn write_xlsx<P: AsRef<Path>>(path: P, pages: &[LayoutRegion]) -> Result<()> {
+    let data = document_to_xlsx(pages)?;
+    std::fs::write(path, data)?;
+    Ok(())
+}
+
+pub fn tables_to_xlsx(tables: &[SheetTable]) -> Result<Vec<u8>> {
+    let mut sheet_names: Vec<String> = (1..=tables.len()).map(|i| format!("Table {}", i)).collect();
+    sheet_names.push("Metadata".to_string());
+
+    let mut zip = ZipWriter::new();
+    zip.add_file(
+        "[Content_Types].xml",
+        content_types(sheet_names.len()).as_bytes(),
+    )?;
+    zip.add_file("_rels/.rels", ROOT_RELS.as_bytes())?;
+    zip.add_file(
+        "xl/workbook.xml",
+        workbook(sheet_names.as_slice()).as_bytes(),
+    )?;
+    zip.add_file(
+        "xl/_rels/workbook.xml.rels",
+        workbook_rels(sheet_names.len()).as_bytes(),
+    )?;
+    zip.add_file("xl/styles.xml", STYLES.as_bytes())?;
+    for (i, table) in tables.iter().enumerate() {
+        let name = format!("xl/worksheets/sheet{}.xml", i + 1);
+        zip.add_file(name.as_str(), table_sheet(table.table).as_bytes())?;
+    }
+    let name = format!("xl/worksheets/sheet{}.xml", sheet_names.len());
+    zip.add_file(
+        name.as_str(),
+        metadata_sheet(tables, sheet_names.as_slice()).as_bytes(),
+    )?;
+    Ok(zip.finish())
+}
+
+#[derive(Clone)]
+enum CellValue {
+    Text(String),
+    Number(f64),
+    // a number read from "15%", stored as 0.15 and shown as a percentage
+    Percent(f64),
+}
+
+fn table_sheet(table: &TableResult) -> String {
+    let mut rows: Vec<Vec<(u32, CellValue)>> = vec![Vec::new(); table.row_count() as usize];
+    let mut merges = Vec::new();
+    for cell in table.cells().iter() {
+        let Some(row) = rows.get_mut(cell.row as usize) else {
+            continue;
+        };
+        let text = cell.text();
+        let content = text.trim();
+        let value = match parse_number(content) {
+            Some(v) if content.trim_end_matches(')').trim_end().ends_with('%') => {
+                CellValue::Percent(v)
+            }
+            Some(v) => CellValue::Number(v),
+            None => CellValue::Text(content.to_string()),
+        };
+        row.push((cell.col, value));
+        if cell.row_span > 1 || cell.col_span > 1 {
+            merges.push(format!(
+                "{}:{}",
+                cell_ref(cell.row, cell.col),
+                cell_ref(cell.row + cell.row_span - 1, cell.col + cell.col_span - 1)
+            ));
+        }
+    }
+    for row in rows.iter_mut() {
+        row.sort_by_key(|(col, _)| *col);
+    }
+    sheet_xml(rows.as_slice(), merges.as_slice())
+}
+
+fn metadata_sheet(tables: &[SheetTable], sheet_names: &[String]) -> String {
+    let header = ["sheet", "page", "x1", "y1", "x2", "y2", "rows", "cols"];
+    let mut rows: Vec<Vec<(u32, CellValue)>> = Vec::new();
+    rows.push(
+        header
+            .iter()
+            .enumerate()
+            .map(|(i, h)| (i as u32, CellValue::Text(h.to_string())))
+            .collect(),
+    );
+    for (table, name) in tables.iter().zip(sheet_names.iter()) {
+        let [x1, y1, x2, y2] = table.bbox;
+        let values = [
+            CellValue::Text(name.to_owned()),
+            CellValue::Number((table.page + 1) as f64),
+            CellValue::Number(x1 as f64),
+            CellValue::Number(y1 as f64),
+            CellValue::Number(x2 as f64),
+            CellValue::Number(y2 as f64),
+            CellValue::Number(table.table.row_count() as f64),
+            CellValue::Number(table.table.col_count() as f64),
+        ];
+        rows.push(
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| (i as u32, v))
+                .collect(),
+        );
+    }
+    sheet_xml(rows.as_slice(), &[])
+}
+
+fn sheet_xml(rows: &[Vec<(u32, CellValue)>], merges: &[String]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <worksheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\"><sheetData>",
+    );
+    for (row_id, row) in rows.iter().enumerate() {
+        xml.push_str(format!("<row r=\"{}\">", row_id + 1).as_str());
+        for (col, value) in row.iter() {
+            let r = cell_ref(row_id as u32, *col);
+            match value {
+                CellValue::Number(v) => {
+                    xml.push_str(format!("<c r=\"{}\"><v>{}</v></c>", r, v).as_str());
+                }
+                CellValue::Percent(v) => {
+                    xml.push_str(format!("<c r=\"{}\" s=\"2\"><v>{}</v></c>", r, v).as_str());
+                }
+                CellValue::Text(t) => {
+                    xml.push_str(
+                        format!(
+                            "<c r=\"{}\" t=\"inlineStr\" s=\"1\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+                            r,
+                            escape_xml(t)
+                        )
+                        .as_str(),
+                    );
+                }
+            }
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData>");
+    if !merges.is_empty() {
+        xml.push_str(format!("<mergeCells count=\"{}\">", merges.len()).as_str());
+        for merge in merges.iter() {
+            xml.push_str(format!("<mergeCell ref=\"{}\"/>", merge).as_str());
+        }
+        xml.push_str("</mergeCells>");
+    }
+    xml.push_str("</worksheet>");
+    xml
+}
+
+// (0, 0) -> "A1", (1, 27) -> "AB2"
+fn cell_ref(row: u32, col: u32) -> String {
+    let mut name = Vec::new();
+    let mut col = col + 1;
+    while col > 0 {
+        let rem = (col - 1) % 26;
+        name.push(b'A' + rem as u8);
+        col = (col - 1) / 26;
+    }
+    name.reverse();
+    format!("{}{}", String::from_utf8(name).unwrap_or_default(), row + 1)
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\t' | '\n' | '\r' => res.push(c),
+            // control characters are not allowed in xml 1.0
+            c if (c as u32) < 0x20 => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+fn content_types(sheet_num: usize) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+         <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+         <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+         <Override PartName=\"/xl/workbook.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml\"/>\
+         <Override PartName=\"/xl/styles.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml\"/>",
+    );
+    for i in 1..=sheet_num {
+        xml.push_str(
+            format!(
+                "<Override PartName=\"/xl/worksheets/sheet{}.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>",
+                i
+            )
+            .as_str(),
+        );
+    }
+    xml.push_str("</Types>");
+    xml
+}
+
+fn workbook(sheet_names: &[String]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <workbook xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" \
+         xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\"><sheets>",
+    );
+    for (i, name) in sheet_names.iter().enumerate() {
+        xml.push_str(
+            format!(
+                "<sheet name=\"{}\" sheetId=\"{}\" r:id=\"rId{}\"/>",
+                escape_xml(name),
+                i + 1,
+                i + 1
+            )
+            .as_str(),
+        );
+    }
+    xml.push_str("</sheets></workbook>");
+    xml
+}
+
+fn workbook_rels(sheet_num: usize) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">",
+    );
+    for i in 1..=sheet_num {
+        xml.push_str(
+            format!(
+                "<Relationship Id=\"rId{}\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" Target=\"worksheets/sheet{}.xml\"/>",
+                i, i
+            )
+            .as_str(),
+        );
+    }
+    xml.push_str(
+        format!(
+            "<Relationship Id=\"rId{}\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles\" Target=\"styles.xml\"/>",
+            sheet_num + 1
+        )
+        .as_str(),
+    );
+    xml.push_str("</Relationships>");
+    xml
+}
+
+const ROOT_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" Target=\"xl/workbook.xml\"/>\
+</Relationships>";
+
+// style 1 wraps text so multi-line cells stay readable, style 2 is the built-in "0.00%" format
+const STYLES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<styleSheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+<fonts count=\"1\"><font><sz val=\"11\"/><name val=\"Calibri\"/></font></fonts>\
+<fills count=\"2\"><fill><patternFill patternType=\"none\"/></fill><fill><patternFill patternType=\"gray125\"/></fill></fills>\
+<borders count=\"1\"><border><left/><right/><top/><bottom/><diagonal/></border></borders>\
+<cellStyleXfs count=\"1\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\"/></cellStyleXfs>\
+<cellXfs count=\"3\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\"/>\
+<xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\" applyAlignment=\"1\"><alignment wrapText=\"1\" vertical=\"top\"/></xf>\
+<xf numFmtId=\"10\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\" applyNumberFormat=\"1\"/></cellXfs>\
+<cellStyles count=\"1\"><cellStyle name=\"Normal\" xfId=\"0\" builtinId=\"0\"/></cellStyles>\
+</styleSheet>";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::table::table_from_rows;
+
+    #[test]
+    fn table_sheet_formats_percentages() {
+        let rows = vec![vec![
+            "15%".to_string(),
+            "1,200".to_string(),
+            "007".to_string(),
+        ]];
+        let xml = table_sheet(&table_from_rows(rows.as_slice(), 0));
+        assert!(xml.contains("<c r=\"A1\" s=\"2\"><v>0.15</v></c>"));
+        assert!(xml.contains("<c r=\"B1\"><v>1200</v></c>"));
+        assert!(xml.contains("<c r=\"C1\" t=\"inlineStr\" s=\"1\">"));
+    }
+
+    #[test]
+    fn cell_ref_names_columns_like_a_spreadsheet() {
+        assert_eq!(cell_ref(0, 0), "A1");
+        assert_eq!(cell_ref(1, 25), "Z2");
+        assert_eq!(cell_ref(1, 27), "AB2");
+        assert_eq!(cell_ref(9, 701), "ZZ10");
+        assert_eq!(cell_ref(0, 702), "AAA1");
+    }
+
+    #[test]
+    fn escape_xml_drops_control_characters() {
+        assert_eq!(escape_xml("a<b & \"c\"\u{1}"), "a&lt;b &amp; &quot;c&quot;");
+    }
+}
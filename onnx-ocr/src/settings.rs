@@ -1,9 +1,12 @@
 use crate::doc_text_ori;
+use crate::text_recognition::predictor::Language;
 
 pub struct Settings {
     pub text_det_model_path: String,
     pub text_rec_model_path: String,
     pub text_charactor_list_path: String,
+    // (language, model path, character path) of the optional language specific recognizers
+    pub lang_text_rec_models: Vec<(Language, String, String)>,
     pub wired_table_cell_det_model_path: String,
     pub wireless_table_cell_det_model_path: String,
     pub doc_layout_model_path: String,
@@ -13,6 +16,10 @@ pub struct Settings {
     pub wireless_table_structure_model_path: String,
     pub wireless_table_structure_character_path: String,
     pub doc_text_ori_model_path: String,
+    pub formula_rec_model_path: String,
+    pub formula_rec_character_path: String,
+    pub chart_rec_model_path: String,
+    pub chart_rec_character_path: String,
 }
 
 fn model_file(fix: &str, base_path: &str) -> String {
@@ -27,6 +34,20 @@ impl Settings {
         let text_rec_model_path = model_file("PP-OCRv5_server_rec/model.onnx", model_base_path);
         let text_charactor_list_path =
             model_file("PP-OCRv5_server_rec/character.json", model_base_path);
+        let lang_text_rec_models = [
+            (Language::English, "en_PP-OCRv5_mobile_rec"),
+            (Language::Korean, "korean_PP-OCRv5_mobile_rec"),
+            (Language::Cyrillic, "cyrillic_PP-OCRv5_mobile_rec"),
+        ]
+        .into_iter()
+        .map(|(language, model_dir)| {
+            (
+                language,
+                model_file(&format!("{}/model.onnx", model_dir), model_base_path),
+                model_file(&format!("{}/character.json", model_dir), model_base_path),
+            )
+        })
+        .collect();
 
         let doc_layout_model_path =
             model_file("pp-DocLayout_plus-L_infer/model.onnx", model_base_path);
@@ -49,11 +70,18 @@ impl Settings {
             model_file("SLANeXt_wireless/character.json", model_base_path);
         let doc_text_ori_model_path =
             model_file("PP-LCNet_x1_0_doc_ori/model.onnx", model_base_path);
+        let formula_rec_model_path = model_file("PP-FormulaNet_plus-L/model.onnx", model_base_path);
+        let formula_rec_character_path =
+            model_file("PP-FormulaNet_plus-L/character.json", model_base_path);
+
+        let chart_rec_model_path = model_file("PP-Chart2Table/model.onnx", model_base_path);
+        let chart_rec_character_path = model_file("PP-Chart2Table/character.json", model_base_path);
 
         return Settings {
             text_det_model_path,
             text_rec_model_path,
             text_charactor_list_path,
+            lang_text_rec_models,
             doc_layout_model_path,
             wired_table_cell_det_model_path,
             wireless_table_cell_det_model_path,
@@ -63,6 +91,10 @@ impl Settings {
             wireless_table_structure_model_path,
             wireless_table_structure_character_path,
             doc_text_ori_model_path,
+            formula_rec_model_path,
+            formula_rec_character_path,
+            chart_rec_model_path,
+            chart_rec_character_path,
         };
     }
 }
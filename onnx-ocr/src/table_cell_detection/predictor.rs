@@ -5,7 +5,7 @@ use std::rc::Rc;
 use anyhow::Result;
 use image::RgbImage;
 
-use ndarray::Ix2;
+use ndarray::{Axis, Ix2, concatenate};
 use ort::{
     inputs,
     session::Session,
@@ -13,7 +13,10 @@ use ort::{
 };
 
 use crate::{
-    common::{imgproc::load_image, onnx::load_session},
+    common::{
+        imgproc::load_image,
+        onnx::{load_session, split_batch_boxes},
+    },
     table_cell_detection::{postprocess::PostProcessor, preprocess::PreProcessor},
 };
 
@@ -48,34 +51,59 @@ impl TableCellDetector {
     }
 
     pub fn predict_image(&self, img: &RgbImage) -> Result<Vec<TableCelltResult>> {
-        let img_width = img.width();
-        let img_height = img.height();
-        let pre_output = self.pre_processor.process(img)?;
-        let input = pre_output.get_input_as_ndarray();
+        let mut results = self.predict_batch(std::slice::from_ref(img))?;
+        Ok(results.remove(0))
+    }
+
+    pub fn predict_batch(&self, images: &[RgbImage]) -> Result<Vec<Vec<TableCelltResult>>> {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
+        let batch_size = images.len();
+        let mut pre_outputs = Vec::with_capacity(batch_size);
+        for img in images.iter() {
+            pre_outputs.push(self.pre_processor.process(img)?);
+        }
+        // all images are resized to the same 640x640 input
+        let views: Vec<_> = pre_outputs.iter().map(|p| p.input.view()).collect();
+        let input = concatenate(Axis(0), views.as_slice())?;
+        let im_shape: Vec<f32> = pre_outputs
+            .iter()
+            .flat_map(|p| [p.input_height as f32, p.input_width as f32])
+            .collect();
+        let scale_factor: Vec<f32> = pre_outputs
+            .iter()
+            .flat_map(|p| [p.ratio_h, p.ratio_w])
+            .collect();
+
         let mut sess = self.sess.borrow_mut();
+        let outputs = sess.run(inputs![
+            "image" => TensorRef::from_array_view(&input)?,
+            "im_shape" => Tensor::from_array(([batch_size, 2], im_shape))?,
+            "scale_factor" => Tensor::from_array(([batch_size, 2], scale_factor))?
+        ])?;
 
-        let outputs =
-            sess
-            .run(inputs![
-                "image" =>TensorRef::from_array_view(&input).unwrap(), 
-                "im_shape"=>Tensor::from_array(([1, 2],vec![640.0_f32,640.0])).unwrap(),
-                "scale_factor"=>Tensor::from_array(([1, 2],vec![pre_output.ratio_h,pre_output.ratio_w])).unwrap()]
-            ).unwrap();
+        let output = outputs["fetch_name_0"].try_extract_array::<f32>()?;
+        let boxes = output.into_dimensionality::<Ix2>()?.to_owned();
+        let bbox_num: Vec<i32> = match outputs.get("fetch_name_1") {
+            Some(num) => num.try_extract_array::<i32>()?.iter().copied().collect(),
+            None => vec![boxes.nrows() as i32],
+        };
 
-        let output = outputs["fetch_name_0"].try_extract_array::<f32>().unwrap();
-        let preds = output.squeeze();
-        let bitmap = preds.into_dimensionality::<Ix2>()?.to_owned();
-        let boxes_result =
-            self.post_processor
-                .process(&bitmap, img_width as f32, img_height as f32)?;
-        let mut results = Vec::new();
-        for obj in boxes_result.iter() {
-            let det_res = TableCelltResult {
-                label: "cell".to_string(),
-                coordinate: obj.coordinate,
-                score: obj.score,
-            };
-            results.push(det_res);
+        let mut results = Vec::with_capacity(batch_size);
+        for (img, bitmap) in images.iter().zip(split_batch_boxes(&boxes, &bbox_num)) {
+            let boxes_result =
+                self.post_processor
+                    .process(&bitmap, img.width() as f32, img.height() as f32)?;
+            let cells = boxes_result
+                .iter()
+                .map(|obj| TableCelltResult {
+                    label: "cell".to_string(),
+                    coordinate: obj.coordinate,
+                    score: obj.score,
+                })
+                .collect();
+            results.push(cells);
         }
         Ok(results)
     }
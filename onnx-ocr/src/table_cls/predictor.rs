@@ -4,7 +4,7 @@ use crate::{
 };
 use anyhow::Result;
 use image::RgbImage;
-use ndarray::Array4;
+use ndarray::{Array4, Axis, Ix2};
 use ort::{inputs, session::Session, value::TensorRef};
 use std::{cell::RefCell, path::Path, rc::Rc};
 
@@ -35,36 +35,37 @@ impl TableClsPredictor {
     }
 
     pub fn predict_image(&self, img: &RgbImage) -> Result<TableType> {
-        let input_img = self.pre_processor.process(&img);
+        let mut results = self.predict_batch(std::slice::from_ref(img))?;
+        Ok(results.remove(0))
+    }
 
-        let input_width = input_img.width();
-        let input_height = input_img.height();
-        let mut input = Array4::<f32>::zeros((1, 3, input_height as usize, input_width as usize));
-        for y in 0..input_height as usize {
-            for x in 0..input_width as usize {
-                let pixel = input_img.get_pixel(x as u32, y as u32);
-                let [r, g, b] = pixel.0;
-                input[[0, 0, y, x]] = r;
-                input[[0, 1, y, x]] = g;
-                input[[0, 2, y, x]] = b;
-            }
+    pub fn predict_batch(&self, images: &[RgbImage]) -> Result<Vec<TableType>> {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
+        // every image is resized and center cropped to 224x224
+        let mut input = Array4::<f32>::zeros((images.len(), 3, 224, 224));
+        for (i, img) in images.iter().enumerate() {
+            self.pre_processor
+                .process_into(img, input.index_axis_mut(Axis(0), i));
         }
         let mut sess = self.sess.borrow_mut();
-        let outputs = sess
-            .run(inputs![
-                "x" =>TensorRef::from_array_view(&input).unwrap(),
-            ])
-            .unwrap();
+        let outputs = sess.run(inputs![
+            "x" => TensorRef::from_array_view(&input)?,
+        ])?;
 
         let output = outputs["fetch_name_0"].try_extract_array::<f32>()?;
-        let scores = output.squeeze();
-
-        let wired_score = scores[0];
-        let wireless_score = scores[1];
-        if wired_score > wireless_score {
-            return Ok(TableType::Wired);
-        } else {
-            return Ok(TableType::Wireless);
+        let scores = output.into_dimensionality::<Ix2>()?;
+        let mut results = Vec::with_capacity(images.len());
+        for row in scores.axis_iter(Axis(0)) {
+            let wired_score = row[0];
+            let wireless_score = row[1];
+            if wired_score > wireless_score {
+                results.push(TableType::Wired);
+            } else {
+                results.push(TableType::Wireless);
+            }
         }
+        Ok(results)
     }
 }
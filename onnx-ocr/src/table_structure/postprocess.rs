@@ -55,7 +55,8 @@ impl PostProcessor {
             match label {
                 Some(ls) => {
                     labels.push(ls.to_owned());
-                    if ls == "td" || ls == "</td>" || ls == "<td></td>" {
+                    // one box per cell, `<td` opens a cell carrying span attributes
+                    if ls == "<td>" || ls == "<td" || ls == "<td></td>" {
                         bbox_list.push(bbox);
                     }
                 }
@@ -8,10 +8,8 @@ use std::{cell::RefCell, path::Path, rc::Rc};
 
 use crate::common::{imgproc::load_image, onnx::load_session};
 
-use crate::table_structure::{
-    postprocess::{PostProcessor, TableStructure},
-    preprocess::PreProcessor,
-};
+pub use crate::table_structure::postprocess::TableStructure;
+use crate::table_structure::{postprocess::PostProcessor, preprocess::PreProcessor};
 
 pub struct TableStructurePredictor {
     sess: Rc<RefCell<Session>>,
@@ -50,7 +48,7 @@ impl TableStructurePredictor {
         let mut sess = self.sess.borrow_mut();
 
         let outputs = sess
-            .run(inputs!["x" => TensorRef::from_array_view(&input).unwrap()])
+            .run(inputs!["x" => TensorRef::from_array_view(input).unwrap()])
             .unwrap();
 
         let bbox_logits = outputs["fetch_name_0"].try_extract_array::<f32>().unwrap();
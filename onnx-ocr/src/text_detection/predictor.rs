@@ -6,7 +6,7 @@ use imageproc::{
     geometric_transformations::{Interpolation, warp_into},
     point::Point,
 };
-use ndarray::Ix2;
+use ndarray::{Array2, Array4, Axis, Ix4, s};
 use ort::{inputs, session::Session, value::TensorRef};
 
 use crate::{
@@ -67,29 +67,62 @@ impl TextDetectionPredictor {
     }
 
     pub fn predict_image(&self, img: &RgbImage) -> Result<DetectResult> {
-        let pre_output = self.pre_processor.process(img)?;
-        let input = pre_output.get_input_as_ndarray();
-        let mut sess = self.sess.borrow_mut();
+        let mut results = self.predict_batch(std::slice::from_ref(img))?;
+        Ok(results.remove(0))
+    }
 
-        let outputs = sess
-            .run(inputs!["x" => TensorRef::from_array_view(&input).unwrap()])
-            .unwrap();
+    pub fn predict_batch(&self, images: &[RgbImage]) -> Result<Vec<DetectResult>> {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut pre_outputs = Vec::with_capacity(images.len());
+        for img in images.iter() {
+            pre_outputs.push(self.pre_processor.process(img)?);
+        }
+        // every image is resized to a multiple of 32 on its own, pad them to the largest one
+        let max_h = pre_outputs.iter().map(|p| p.input_height).max().unwrap() as usize;
+        let max_w = pre_outputs.iter().map(|p| p.input_width).max().unwrap() as usize;
+        let mut input = Array4::<f32>::zeros((images.len(), 3, max_h, max_w));
+        for (i, pre_output) in pre_outputs.iter().enumerate() {
+            let h = pre_output.input_height as usize;
+            let w = pre_output.input_width as usize;
+            input
+                .slice_mut(s![i, .., ..h, ..w])
+                .assign(&pre_output.input.index_axis(Axis(0), 0));
+        }
 
-        let output = outputs["fetch_name_0"].try_extract_array::<f32>().unwrap();
-        let preds = output.squeeze();
-        let bitmap = preds.into_dimensionality::<Ix2>()?.to_owned();
+        let mut sess = self.sess.borrow_mut();
+        let outputs = sess.run(inputs!["x" => TensorRef::from_array_view(&input)?])?;
+        let output = outputs["fetch_name_0"].try_extract_array::<f32>()?;
+        let preds = output.into_dimensionality::<Ix4>()?;
+
+        let mut results = Vec::with_capacity(images.len());
+        for (i, pre_output) in pre_outputs.iter().enumerate() {
+            let h = pre_output.input_height as usize;
+            let w = pre_output.input_width as usize;
+            let bitmap = preds.slice(s![i, 0, ..h, ..w]).to_owned();
+            results.push(self.postprocess(&bitmap, pre_output.ratio_w, pre_output.ratio_h)?);
+        }
+        Ok(results)
+    }
 
-        let boxes_result = self.post_processor.process(&bitmap)?;
+    fn postprocess(
+        &self,
+        bitmap: &Array2<f32>,
+        ratio_w: f32,
+        ratio_h: f32,
+    ) -> Result<DetectResult> {
+        let boxes_result = self.post_processor.process(bitmap)?;
         let mut polys = Vec::new();
         let mut scores = Vec::new();
 
         for b in boxes_result.iter() {
             let [tl, tr, dr, dl] = b.bbox;
             let [otl, otr, odr, odl] = [
-                rescale_point(&tl, pre_output.ratio_w, pre_output.ratio_h),
-                rescale_point(&tr, pre_output.ratio_w, pre_output.ratio_h),
-                rescale_point(&dr, pre_output.ratio_w, pre_output.ratio_h),
-                rescale_point(&dl, pre_output.ratio_w, pre_output.ratio_h),
+                rescale_point(&tl, ratio_w, ratio_h),
+                rescale_point(&tr, ratio_w, ratio_h),
+                rescale_point(&dr, ratio_w, ratio_h),
+                rescale_point(&dl, ratio_w, ratio_h),
             ];
             let poly = Quad::new(otl, otr, odr, odl);
             polys.push(poly);
@@ -1,17 +1,16 @@
 use anyhow::Result;
 use image::{
-    Rgb, Rgb32FImage, RgbImage,
+    Rgb, RgbImage,
     imageops::{FilterType, resize},
 };
 use ndarray::Array4;
 
-use crate::common::imgproc::convert_rgb_to_rgb32f;
+use crate::common::imgproc::{ChannelOrder, NormalizeParams, normalize_to_nchw};
 
 pub struct PreProcessor {
     max_side_limit: usize,
     limit_side_len: usize,
-    alpha: [f32; 3],
-    beta: [f32; 3],
+    normalize_params: NormalizeParams,
 }
 
 pub struct PreOutput {
@@ -20,36 +19,15 @@ pub struct PreOutput {
     pub ratio_w: f32,
     pub input_width: u32,
     pub input_height: u32,
-    pub input: Rgb32FImage,
-}
-impl PreOutput {
-    pub fn get_input_as_ndarray(&self) -> Array4<f32> {
-        let mut input =
-            Array4::<f32>::zeros((1, 3, self.input_height as usize, self.input_width as usize));
-
-        for y in 0..self.input_height as usize {
-            for x in 0..self.input_width as usize {
-                let pixel = self.input.get_pixel(x as u32, y as u32);
-                let [r, g, b] = pixel.0;
-                input[[0, 0, y, x]] = r;
-                input[[0, 1, y, x]] = g;
-                input[[0, 2, y, x]] = b;
-            }
-        }
-        return input;
-    }
+    pub input: Array4<f32>,
 }
 
 impl Default for PreProcessor {
     fn default() -> Self {
-        let alpha = [1.0 / 0.229, 1.0 / 0.224, 1.0 / 0.225];
-        let beta = [-0.485 / 0.229, -0.456 / 0.224, -0.406 / 0.225];
-
         PreProcessor {
             max_side_limit: 4000,
             limit_side_len: 960,
-            alpha,
-            beta,
+            normalize_params: NormalizeParams::imagenet(ChannelOrder::Bgr),
         }
     }
 }
@@ -63,10 +41,7 @@ impl PreProcessor {
         } else {
             img.to_owned()
         };
-        let mut result = self.resize(&paded_image)?;
-        let nimg = self.normalize(&result.input);
-        result.input = nimg;
-        Ok(result)
+        self.resize(&paded_image)
     }
     fn pad_image(&self, img: &RgbImage) -> RgbImage {
         let pw = std::cmp::max(img.width(), 32);
@@ -81,23 +56,6 @@ impl PreProcessor {
         new_img
     }
 
-    fn normalize(&self, img: &Rgb32FImage) -> Rgb32FImage {
-        let w = img.width();
-        let h = img.height();
-        let mut nimg = Rgb32FImage::new(w, h);
-        for x in 0..w {
-            for y in 0..h {
-                let pixel = img.get_pixel(x, y);
-                let [r, g, b] = pixel.0;
-                let r = r as f32 * self.alpha[0] + self.beta[0];
-                let g = g as f32 * self.alpha[1] + self.beta[1];
-                let b = b as f32 * self.alpha[2] + self.beta[2];
-                nimg.put_pixel(x, y, Rgb([r, g, b]));
-            }
-        }
-        return nimg;
-    }
-
     fn resize(&self, img: &RgbImage) -> Result<PreOutput> {
         let h = img.height();
         let w = img.width();
@@ -128,7 +86,7 @@ impl PreProcessor {
                 input_height: rh,
                 ratio_h: 1.0,
                 ratio_w: 1.0,
-                input: convert_rgb_to_rgb32f(img),
+                input: normalize_to_nchw(img, &self.normalize_params),
             };
             return Ok(result);
         }
@@ -139,7 +97,7 @@ impl PreProcessor {
             input_height: rh,
             ratio_w: w as f32 / rw as f32,
             ratio_h: h as f32 / rh as f32,
-            input: convert_rgb_to_rgb32f(&rimg),
+            input: normalize_to_nchw(&rimg, &self.normalize_params),
         };
         return Ok(result);
     }
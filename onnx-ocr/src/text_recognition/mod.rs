@@ -2,3 +2,4 @@ mod postprocess;
 mod preprocess;
 
 pub mod predictor;
+pub mod script;
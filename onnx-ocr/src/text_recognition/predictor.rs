@@ -1,6 +1,6 @@
 use anyhow::Result;
 use image::RgbImage;
-use ndarray::{Array4, Ix2};
+use ndarray::Ix2;
 use ort::{inputs, session::Session, value::TensorRef};
 use std::{cell::RefCell, collections::HashMap, fs::File, io::BufReader, path::Path, rc::Rc};
 
@@ -10,6 +10,25 @@ use crate::{
     text_recognition::{postprocess::PostProcessor, preprocess::PreProcessor},
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Chinese,
+    English,
+    Korean,
+    Cyrillic,
+}
+
+impl Language {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Language::Chinese => "chinese",
+            Language::English => "english",
+            Language::Korean => "korean",
+            Language::Cyrillic => "cyrillic",
+        }
+    }
+}
+
 pub struct TextRecognitionPredictor {
     sess: Rc<RefCell<Session>>,
     character_dict: HashMap<u32, String>,
@@ -46,28 +65,29 @@ impl TextRecognitionPredictor {
     }
 
     pub fn predict(&self, images: Vec<RgbImage>) -> Result<Vec<(String, f32)>> {
+        let predicted_text = self
+            .predict_with_mean_score(images)?
+            .into_iter()
+            .map(|(content, max_score, _)| (content, max_score))
+            .collect();
+        Ok(predicted_text)
+    }
+
+    // (text, max score, mean score of the decoded characters), a single confident character
+    // lifts the max score so the mean tells how well the whole line was read
+    pub fn predict_with_mean_score(
+        &self,
+        images: Vec<RgbImage>,
+    ) -> Result<Vec<(String, f32, f32)>> {
         let mut predicted_text = Vec::new();
         for img in images {
-            let input_img = self.pre_processor.process(&img);
-            let height = input_img.height();
-            let width = input_img.width();
+            let input = self.pre_processor.process(&img);
+            let (_, _, height, width) = input.dim();
             if width < height {
-                predicted_text.push(("".to_string(), 1.0));
+                predicted_text.push(("".to_string(), 1.0, 1.0));
                 continue;
             }
 
-            let mut input = Array4::<f32>::zeros((1, 3, height as usize, width as usize));
-
-            for y in 0..height as usize {
-                for x in 0..width as usize {
-                    let pixel = input_img.get_pixel(x as u32, y as u32);
-                    let [r, g, b] = pixel.0;
-                    input[[0, 0, y, x]] = b;
-                    input[[0, 1, y, x]] = g;
-                    input[[0, 2, y, x]] = r;
-                }
-            }
-
             let mut sess = self.sess.borrow_mut();
             let outputs = sess
                 .run(inputs!["x" => TensorRef::from_array_view(&input)?])
@@ -86,18 +106,27 @@ impl TextRecognitionPredictor {
             let idx_score = self.post_processor.process(&preds)?;
             let mut content = String::new();
             let mut max_score = 0.0;
+            let mut total_score = 0.0;
+            let mut num_char = 0;
             for (id, score) in idx_score.iter() {
                 if *id == 0 {
                     content.push_str("");
                 } else {
                     let s = self.character_dict.get(id).unwrap();
-                    content.push_str(s.as_str())
+                    content.push_str(s.as_str());
+                    total_score += score;
+                    num_char += 1;
                 }
                 if score > &max_score {
                     max_score = score.to_owned()
                 }
             }
-            predicted_text.push((content, max_score));
+            let mean_score = if num_char > 0 {
+                total_score / num_char as f32
+            } else {
+                0.0
+            };
+            predicted_text.push((content, max_score, mean_score));
         }
         Ok(predicted_text)
     }
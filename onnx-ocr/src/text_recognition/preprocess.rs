@@ -1,12 +1,16 @@
 use image::{
-    Rgb, Rgb32FImage, RgbImage,
+    Rgb, RgbImage,
     imageops::{FilterType, resize},
 };
+use ndarray::Array4;
+
+use crate::common::imgproc::{ChannelOrder, NormalizeParams, normalize_to_nchw};
 
 pub struct PreProcessor {
     rec_image_shape: [u32; 2],
     max_width: u32,
     ratio: f32,
+    normalize_params: NormalizeParams,
 }
 
 impl Default for PreProcessor {
@@ -15,12 +19,18 @@ impl Default for PreProcessor {
             rec_image_shape: [48, 320],
             max_width: 3200,
             ratio: 320.0 / 48.0,
+            normalize_params: NormalizeParams::new(
+                1.0 / 255.0,
+                [0.5; 3],
+                [0.5; 3],
+                ChannelOrder::Bgr,
+            ),
         }
     }
 }
 
 impl PreProcessor {
-    pub fn process(&self, img: &RgbImage) -> Rgb32FImage {
+    pub fn process(&self, img: &RgbImage) -> Array4<f32> {
         let width = img.width();
         let height = img.height();
         let target_h = self.rec_image_shape[0];
@@ -31,7 +41,7 @@ impl PreProcessor {
         if target_w > self.max_width {
             target_w = self.max_width;
             let out_image = resize(img, target_w, target_h, FilterType::Triangle);
-            self.normalize(&out_image)
+            normalize_to_nchw(&out_image, &self.normalize_params)
         } else {
             let resized_w = (target_h as f32 * ratio).floor() as u32;
             if resized_w < target_w {
@@ -45,24 +55,7 @@ impl PreProcessor {
                     out_image.put_pixel(x, y, pixel.to_owned());
                 }
             }
-            self.normalize(&out_image)
-        }
-    }
-
-    fn normalize(&self, img: &RgbImage) -> Rgb32FImage {
-        let w = img.width();
-        let h = img.height();
-        let mut nimg = Rgb32FImage::new(w, h);
-        for x in 0..w {
-            for y in 0..h {
-                let pixel = img.get_pixel(x, y);
-                let [r, g, b] = pixel.0;
-                let r = ((r as f32 / 255.0) - 0.5) / 0.5;
-                let g = ((g as f32 / 255.0) - 0.5) / 0.5;
-                let b = ((b as f32 / 255.0) - 0.5) / 0.5;
-                nimg.put_pixel(x, y, Rgb([r, g, b]));
-            }
+            normalize_to_nchw(&out_image, &self.normalize_params)
         }
-        return nimg;
     }
 }
@@ -0,0 +1,63 @@
+use crate::text_recognition::predictor::Language;
+
+#[derive(Debug, Default)]
+struct ScriptCount {
+    han: usize,
+    latin: usize,
+    hangul: usize,
+    cyrillic: usize,
+}
+
+fn is_han(c: char) -> bool {
+    matches!(c,
+        '\u{4E00}'..='\u{9FFF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{F900}'..='\u{FAFF}'
+        // kana is recognized by the chinese model as well
+        | '\u{3040}'..='\u{30FF}')
+}
+
+fn is_hangul(c: char) -> bool {
+    matches!(c,
+        '\u{AC00}'..='\u{D7AF}' | '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}')
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, '\u{0400}'..='\u{052F}')
+}
+
+fn is_latin(c: char) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, '\u{00C0}'..='\u{024F}')
+}
+
+// identify the language of a recognized line from the unicode script of its letters.
+// digits and punctuation are shared by all scripts and ignored, latin letters only decide
+// the language when no other script is present since every model also reads latin.
+pub fn identify_language(text: &str) -> Option<Language> {
+    let mut count = ScriptCount::default();
+    for c in text.chars() {
+        if is_han(c) {
+            count.han += 1;
+        } else if is_hangul(c) {
+            count.hangul += 1;
+        } else if is_cyrillic(c) {
+            count.cyrillic += 1;
+        } else if is_latin(c) {
+            count.latin += 1;
+        }
+    }
+    let script = [
+        (count.cyrillic, Language::Cyrillic),
+        (count.hangul, Language::Korean),
+        (count.han, Language::Chinese),
+    ]
+    .into_iter()
+    .filter(|(n, _)| *n > 0)
+    .max_by_key(|(n, _)| *n)
+    .map(|(_, language)| language);
+    match script {
+        Some(language) => Some(language),
+        None if count.latin > 0 => Some(Language::English),
+        None => None,
+    }
+}